@@ -0,0 +1,327 @@
+use lasim::engine;
+use lasim::engine::Cancellation;
+use lasim::engine::GlobalSettings;
+use lasim::engine::ProcessingInstruction;
+
+use clap::Args;
+use clap::Parser;
+use clap::Subcommand;
+
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::io::Write;
+
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_FAILURE: i32 = 1;
+
+#[derive(Parser, Debug)]
+#[command(name = "lasim", version, about = "Lemmy Account Settings Instance Migrator")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Download an account's profile and store it as <username>@<instance>
+    Download {
+        #[command(flatten)]
+        account: AccountArgs,
+        #[command(flatten)]
+        settings: DownloadSettingsArgs,
+    },
+    /// Apply a stored profile to an account
+    Upload {
+        #[command(flatten)]
+        account: AccountArgs,
+        #[command(flatten)]
+        settings: SettingsArgs,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Show what an upload would change, without changing anything
+    Diff {
+        #[command(flatten)]
+        account: AccountArgs,
+        #[command(flatten)]
+        settings: SettingsArgs,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Show what differs between two profiles, without logging in anywhere
+    Compare {
+        /// Stored profile name, or path to a profile file or folder
+        #[arg(long)]
+        profile: String,
+        /// Stored profile name, or path to a profile file or folder, to compare against
+        #[arg(long)]
+        with: String,
+    },
+    /// Show what differs between a profile and an account as it is right now, without changing anything
+    CompareAccount {
+        #[command(flatten)]
+        account: AccountArgs,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Revert the last upload to an account
+    Undo {
+        #[command(flatten)]
+        account: AccountArgs,
+        #[arg(long)]
+        confirm_uploads: Option<bool>,
+    },
+    /// Combine stored profiles into a new stored profile
+    Merge {
+        /// Stored profile to merge (repeat for each profile)
+        #[arg(long = "profile", required = true)]
+        profiles: Vec<String>,
+        /// Stored profile whose profile settings are used
+        #[arg(long)]
+        settings_from: String,
+        /// Name to store the merged profile under
+        #[arg(long)]
+        name: String,
+    },
+    /// Add follows to a stored profile from a Reddit export's subscribed_subreddits.csv
+    ImportReddit {
+        /// Path to subscribed_subreddits.csv
+        #[arg(long)]
+        subreddits: PathBuf,
+        /// CSV file mapping subreddits to communities, one subreddit,name@instance pair per line
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+        /// Instance to search for communities named like the subreddits that are not in the mapping
+        #[arg(long)]
+        search_instance: Option<String>,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Write a stored profile's followed communities to an OPML feed list
+    ExportOpml {
+        /// Path of the OPML file to write
+        #[arg(long)]
+        output: PathBuf,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Add follows to a stored profile from the Lemmy community feeds in an OPML feed list
+    ImportOpml {
+        /// Path of the OPML file to read
+        #[arg(long)]
+        input: PathBuf,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Write a stored profile as an older profile version, for older LASIM versions to read
+    ExportProfile {
+        /// Profile version to write, see the Version Support table in the README
+        #[arg(long)]
+        profile_version: u16,
+        /// Folder to write profile_v<version>.json to
+        #[arg(long)]
+        output: PathBuf,
+        #[command(flatten)]
+        profile: ProfileArgs,
+    },
+    /// Check a profile for malformed entries, duplicates and unknown settings
+    CheckProfile {
+        /// Stored profile name, or path to a profile file or folder (may be omitted if only one is stored)
+        #[arg(long, default_value = "")]
+        profile: String,
+    },
+    /// List the stored profiles
+    Profiles,
+}
+
+#[derive(Args, Debug)]
+struct AccountArgs {
+    /// Instance URL, e.g. lemmy.world
+    #[arg(long)]
+    instance: String,
+    /// Username or email
+    #[arg(long)]
+    username: String,
+    /// Password (prefer the environment variable over the flag)
+    #[arg(long, env = "LASIM_PASSWORD", hide_env_values = true)]
+    password: String,
+    /// 2FA token, if enabled on the account
+    #[arg(long, default_value = "")]
+    two_factor_token: String,
+}
+
+#[derive(Args, Debug)]
+struct ProfileArgs {
+    /// Stored profile to upload, e.g. alice@lemmy.world (may be omitted if only one is stored)
+    #[arg(long, default_value = "")]
+    profile: String,
+}
+
+/// Overrides for the settings saved by the GUI (or the defaults if there are none)
+#[derive(Args, Debug)]
+struct SettingsArgs {
+    #[arg(long)]
+    upload_profile_settings: Option<bool>,
+    #[arg(long)]
+    upload_community_subs: Option<bool>,
+    #[arg(long)]
+    upload_community_blocks: Option<bool>,
+    #[arg(long)]
+    upload_instance_blocks: Option<bool>,
+    #[arg(long)]
+    upload_user_blocks: Option<bool>,
+    #[arg(long)]
+    upload_user_saved_posts: Option<bool>,
+    #[arg(long)]
+    sync_removals: Option<bool>,
+    #[arg(long)]
+    confirm_uploads: Option<bool>,
+    #[arg(long)]
+    write_api_profiles: Option<bool>,
+    #[arg(long)]
+    upload_display_name: Option<bool>,
+    #[arg(long)]
+    upload_bio: Option<bool>,
+    #[arg(long)]
+    upload_matrix_user_id: Option<bool>,
+}
+
+impl SettingsArgs {
+    fn apply(&self, mut global_settings: GlobalSettings) -> GlobalSettings {
+        let overrides = [
+            (self.upload_profile_settings, &mut global_settings.upload_profile_settings),
+            (self.upload_community_subs, &mut global_settings.upload_community_subs),
+            (self.upload_community_blocks, &mut global_settings.upload_community_blocks),
+            (self.upload_instance_blocks, &mut global_settings.upload_instance_blocks),
+            (self.upload_user_blocks, &mut global_settings.upload_user_blocks),
+            (self.upload_user_saved_posts, &mut global_settings.upload_user_saved_posts),
+            (self.sync_removals, &mut global_settings.sync_removals),
+            (self.confirm_uploads, &mut global_settings.confirm_uploads),
+            (self.write_api_profiles, &mut global_settings.write_api_profiles),
+            (self.upload_display_name, &mut global_settings.upload_display_name),
+            (self.upload_bio, &mut global_settings.upload_bio),
+            (self.upload_matrix_user_id, &mut global_settings.upload_matrix_user_id),
+        ];
+
+        for (value, setting) in overrides {
+            if let Some(value) = value {
+                *setting = value;
+            }
+        }
+
+        return global_settings;
+    }
+}
+
+/// Overrides for the saved settings that affect a download
+#[derive(Args, Debug)]
+struct DownloadSettingsArgs {
+    #[arg(long)]
+    write_api_profiles: Option<bool>,
+}
+
+impl DownloadSettingsArgs {
+    fn apply(&self, mut global_settings: GlobalSettings) -> GlobalSettings {
+        if let Some(write_api_profiles) = self.write_api_profiles {
+            global_settings.write_api_profiles = write_api_profiles;
+        }
+
+        return global_settings;
+    }
+}
+
+fn construct_instruction(account: AccountArgs, global_settings: GlobalSettings, profile_name: String) -> ProcessingInstruction {
+    return ProcessingInstruction {
+        instance: account.instance,
+        username: account.username,
+        password: account.password,
+        two_factor_token: account.two_factor_token,
+        global_settings: global_settings,
+        profile_name: profile_name,
+    };
+}
+
+fn confirm_upload(_: &lasim::profile::ProfileChanges) -> bool {
+    if !std::io::stdin().is_terminal() {
+        eprintln!("Cannot confirm upload without a terminal, re-run with --confirm-uploads false to skip confirmation");
+        return false;
+    }
+
+    print!("Apply these changes? [y/N] ");
+    std::io::stdout().flush().ok();
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    let answer = answer.trim().to_lowercase();
+    return answer == "y" || answer == "yes";
+}
+
+pub fn run() -> i32 {
+    let cli = Cli::parse();
+    let saved_settings = crate::read_global_settings().unwrap_or_default();
+    let logger = |text: String| println!("{}", text);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let result = match cli.command {
+        Command::Download { account, settings } => {
+            let instruction = construct_instruction(account, settings.apply(saved_settings), String::new());
+            runtime.block_on(engine::process_download(instruction, logger, &Cancellation::new()))
+        },
+        Command::Upload { account, settings, profile } => {
+            let instruction = construct_instruction(account, settings.apply(saved_settings), profile.profile);
+            runtime.block_on(engine::process_upload(instruction, logger, confirm_upload, &Cancellation::new()))
+        },
+        Command::Diff { account, settings, profile } => {
+            let instruction = construct_instruction(account, settings.apply(saved_settings), profile.profile);
+            runtime.block_on(engine::process_diff(instruction, logger))
+        },
+        Command::Compare { profile, with } => {
+            engine::process_compare(&profile, &with, logger)
+        },
+        Command::CompareAccount { account, profile } => {
+            let instruction = construct_instruction(account, saved_settings, profile.profile);
+            runtime.block_on(engine::process_compare_account(instruction, logger))
+        },
+        Command::Undo { account, confirm_uploads } => {
+            let mut global_settings = saved_settings;
+            global_settings.confirm_uploads = confirm_uploads.unwrap_or(global_settings.confirm_uploads);
+            let instruction = construct_instruction(account, global_settings, String::new());
+            runtime.block_on(engine::process_undo(instruction, logger, confirm_upload, &Cancellation::new()))
+        },
+        Command::Merge { profiles, settings_from, name } => {
+            engine::process_merge(&profiles, &settings_from, &name, logger)
+        },
+        Command::ImportReddit { subreddits, mapping, search_instance, profile } => {
+            runtime.block_on(engine::process_reddit_import(&subreddits, mapping.as_deref(), search_instance.as_deref(), &profile.profile, logger))
+        },
+        Command::ExportOpml { output, profile } => {
+            engine::process_opml_export(&profile.profile, &output, logger)
+        },
+        Command::ImportOpml { input, profile } => {
+            engine::process_opml_import(&input, &profile.profile, logger)
+        },
+        Command::ExportProfile { profile_version, output, profile } => {
+            engine::process_profile_export(&profile.profile, profile_version, &output, logger)
+        },
+        Command::CheckProfile { profile } => {
+            engine::process_profile_check(&profile, logger)
+        },
+        Command::Profiles => {
+            for profile_name in lasim::storage::list_profiles() {
+                logger(profile_name);
+            }
+            Ok(())
+        },
+    };
+
+    match result {
+        Ok(_) => return EXIT_SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_FAILURE;
+        },
+    }
+}
@@ -0,0 +1,277 @@
+use crate::App;
+use crate::EditorPageHandler;
+use crate::SettingField;
+
+use lasim::engine;
+use lasim::profile;
+use lasim::storage;
+use lasim::validation;
+use slint::ComponentHandle;
+use slint::Model;
+use slint::SharedString;
+use slint::VecModel;
+use slint::Weak;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const FOLLOWED_COMMUNITIES: &str = "Followed Communities";
+const BLOCKED_COMMUNITIES: &str = "Blocked Communities";
+const BLOCKED_USERS: &str = "Blocked Users";
+const BLOCKED_INSTANCES: &str = "Blocked Instances";
+const UNSAVED_CHANGES: &str = "Save to keep the changes.";
+
+/// The profile loaded into the Edit page, changed in place until it is saved.
+struct EditedProfile {
+    profile_name: String,
+    profile: profile::ProfileConfiguration,
+}
+
+fn selected_list<'a>(profile: &'a mut profile::ProfileConfiguration, list_name: &str) -> &'a mut Vec<String> {
+    match list_name {
+        BLOCKED_COMMUNITIES => return &mut profile.blocked_communities,
+        BLOCKED_USERS => return &mut profile.blocked_users,
+        BLOCKED_INSTANCES => return &mut profile.blocked_instances,
+        _ => return &mut profile.followed_communities,
+    }
+}
+
+fn show_entries(app: &Weak<App>, edited_profile: &mut EditedProfile) {
+    let list_name = app.unwrap().get_editor_selected_list().to_string();
+    let search_text = app.unwrap().get_editor_search_text().to_lowercase();
+
+    let entries: Vec<SharedString> = selected_list(&mut edited_profile.profile, &list_name).iter()
+        .filter(|entry| entry.to_lowercase().contains(&search_text))
+        .map(SharedString::from)
+        .collect();
+
+    app.unwrap().set_editor_entries(Rc::new(VecModel::from(entries)).into());
+}
+
+fn setting_fields(profile_settings: &profile::ProfileSettings) -> Vec<SettingField> {
+    // Work from the serialized form so every setting gets an editor without listing each field
+    let values = serde_json::to_value(profile_settings).unwrap();
+    let mut fields: Vec<SettingField> = vec![];
+
+    if let Some(value_map) = values.as_object() {
+        for (name, value) in value_map {
+            let text = match value {
+                serde_json::Value::String(text) => text.clone(),
+                serde_json::Value::Array(items) => items.iter().map(|item| item.to_string()).collect::<Vec<String>>().join(", "),
+                serde_json::Value::Null => String::new(),
+                other => other.to_string(),
+            };
+
+            fields.push(SettingField {
+                name: name.clone().into(),
+                is_bool: value.is_boolean(),
+                checked: value.as_bool().unwrap_or(false),
+                text: text.into(),
+            });
+        }
+    }
+
+    return fields;
+}
+
+fn collect_settings(original_settings: &profile::ProfileSettings, fields: impl Iterator<Item = SettingField>) -> Result<profile::ProfileSettings, String> {
+    let mut values = serde_json::to_value(original_settings).unwrap();
+
+    if let Some(value_map) = values.as_object_mut() {
+        for field in fields {
+            let name = field.name.to_string();
+            let text = field.text.trim().to_string();
+            let value = match value_map.get(&name) {
+                Some(serde_json::Value::Bool(_)) => serde_json::Value::Bool(field.checked),
+                Some(serde_json::Value::Array(_)) => {
+                    let mut items: Vec<serde_json::Value> = vec![];
+                    for item in text.split(',').map(|item| item.trim()).filter(|item| !item.is_empty()) {
+                        match item.parse::<i32>() {
+                            Ok(number) => items.push(number.into()),
+                            Err(_) => return Err(format!("ERROR: {} should be a list of numbers, not {}", name, text)),
+                        }
+                    }
+                    serde_json::Value::Array(items)
+                },
+                // An empty field leaves an optional setting unset
+                _ if text.is_empty() => serde_json::Value::Null,
+                _ => serde_json::Value::String(text),
+            };
+            value_map.insert(name, value);
+        }
+    }
+
+    match serde_json::from_value(values) {
+        Ok(profile_settings) => return Ok(profile_settings),
+        Err(e) => return Err(format!("ERROR: Invalid profile settings - {}", e)),
+    }
+}
+
+fn load_profile(app: &Weak<App>, edited: &RefCell<Option<EditedProfile>>) {
+    let profile_name = app.unwrap().get_editor_selected_profile().to_string();
+    let mut status: Vec<String> = vec![];
+    let profile = match engine::read_profile(&profile_name, |text| status.push(text)) {
+        Ok(profile) => profile,
+        Err(e) => {
+            app.unwrap().set_editor_status(e.into());
+            return;
+        },
+    };
+
+    app.unwrap().set_editor_settings(Rc::new(VecModel::from(setting_fields(&profile.profile_settings))).into());
+    status.push(format!("Loaded Profile {}", profile_name));
+    app.unwrap().set_editor_status(status.join("\n").into());
+    app.unwrap().set_editor_profile_loaded(true);
+
+    let mut edited_profile = EditedProfile {
+        profile_name: profile_name,
+        profile: profile,
+    };
+    show_entries(app, &mut edited_profile);
+    *edited.borrow_mut() = Some(edited_profile);
+}
+
+fn add_entry(app: &Weak<App>, edited_profile: &mut EditedProfile) {
+    let entry = app.unwrap().get_editor_new_entry().trim().to_string();
+    let list_name = app.unwrap().get_editor_selected_list().to_string();
+
+    // Instances are blocked by their host alone, everything else is name@host
+    let validation = match list_name.as_str() {
+        BLOCKED_INSTANCES => profile::validate_instance(&entry),
+        _ => profile::validate_name_at_host(&entry),
+    };
+    if let Err(e) = validation {
+        app.unwrap().set_editor_status(e.into());
+        return;
+    }
+    let list = selected_list(&mut edited_profile.profile, &list_name);
+    if list.contains(&entry) {
+        app.unwrap().set_editor_status(format!("{} is already in {}", entry, list_name).into());
+        return;
+    }
+
+    list.push(entry.clone());
+    app.unwrap().set_editor_new_entry(SharedString::new());
+    app.unwrap().set_editor_status(format!("Added {} to {}. {}", entry, list_name, UNSAVED_CHANGES).into());
+    show_entries(app, edited_profile);
+}
+
+fn remove_entry(app: &Weak<App>, edited_profile: &mut EditedProfile, entry: &str) {
+    let list_name = app.unwrap().get_editor_selected_list().to_string();
+
+    selected_list(&mut edited_profile.profile, &list_name).retain(|list_entry| list_entry != entry);
+    app.unwrap().set_editor_status(format!("Removed {} from {}. {}", entry, list_name, UNSAVED_CHANGES).into());
+    show_entries(app, edited_profile);
+}
+
+fn save_profile(app: &Weak<App>, edited_profile: &mut EditedProfile) {
+    let fields = app.unwrap().get_editor_settings();
+    let profile_settings = match collect_settings(&edited_profile.profile.profile_settings, fields.iter()) {
+        Ok(profile_settings) => profile_settings,
+        Err(e) => {
+            app.unwrap().set_editor_status(e.into());
+            return;
+        },
+    };
+    edited_profile.profile.profile_settings = profile_settings;
+
+    let mut status = String::new();
+    let result = storage::get_profile_directory(&edited_profile.profile_name)
+        .and_then(|directory| engine::write_profile(&directory, &edited_profile.profile, |text| status = text));
+    match result {
+        Ok(_) => app.unwrap().set_editor_status(status.into()),
+        Err(e) => app.unwrap().set_editor_status(e.into()),
+    }
+}
+
+fn check_profile(app: &Weak<App>, edited_profile: &EditedProfile) {
+    // Check the profile as it would be saved, including settings changed but not saved yet
+    let fields = app.unwrap().get_editor_settings();
+    let mut profile_local = edited_profile.profile.clone();
+    match collect_settings(&profile_local.profile_settings, fields.iter()) {
+        Ok(profile_settings) => profile_local.profile_settings = profile_settings,
+        Err(e) => {
+            app.unwrap().set_editor_status(e.into());
+            return;
+        },
+    }
+
+    let report = validation::validate_profile(&profile_local);
+    if report.findings.is_empty() {
+        app.unwrap().set_editor_status(format!("No problems found in Profile {}", edited_profile.profile_name).into());
+        return;
+    }
+
+    let findings: Vec<String> = report.findings.iter().map(|finding| finding.to_string()).collect();
+    app.unwrap().set_editor_status(findings.join("\n").into());
+}
+
+/// Keeps the Edit page's profile choice in step with the stored profiles.
+pub fn apply_profile_names(app: &Weak<App>, profile_names: &[SharedString]) {
+    let selected_profile = app.unwrap().get_editor_selected_profile();
+    if !profile_names.contains(&selected_profile) {
+        app.unwrap().set_editor_selected_profile(profile_names.first().cloned().unwrap_or_default());
+    }
+
+    app.unwrap().set_editor_profile_names(Rc::new(VecModel::from(profile_names.to_vec())).into());
+}
+
+/// Binds the Edit page, which changes a stored profile's lists and settings and saves it back in place.
+pub fn bind_editor_page(app: &App) {
+    let list_names: Vec<SharedString> = [FOLLOWED_COMMUNITIES, BLOCKED_COMMUNITIES, BLOCKED_USERS, BLOCKED_INSTANCES].into_iter().map(SharedString::from).collect();
+    app.set_editor_list_names(Rc::new(VecModel::from(list_names)).into());
+    app.set_editor_selected_list(FOLLOWED_COMMUNITIES.into());
+
+    let edited: Rc<RefCell<Option<EditedProfile>>> = Rc::new(RefCell::new(None));
+    let handler = app.global::<EditorPageHandler>();
+
+    handler.on_loaded({
+        let app = app.as_weak();
+        let edited = edited.clone();
+        move || load_profile(&app, &edited)
+    });
+    handler.on_searched({
+        let app = app.as_weak();
+        let edited = edited.clone();
+        move || {
+            if let Some(edited_profile) = edited.borrow_mut().as_mut() {
+                show_entries(&app, edited_profile);
+            }
+        }
+    });
+    handler.on_added({
+        let app = app.as_weak();
+        let edited = edited.clone();
+        move || {
+            if let Some(edited_profile) = edited.borrow_mut().as_mut() {
+                add_entry(&app, edited_profile);
+            }
+        }
+    });
+    handler.on_removed({
+        let app = app.as_weak();
+        let edited = edited.clone();
+        move |entry| {
+            if let Some(edited_profile) = edited.borrow_mut().as_mut() {
+                remove_entry(&app, edited_profile, &entry);
+            }
+        }
+    });
+    handler.on_checked({
+        let app = app.as_weak();
+        let edited = edited.clone();
+        move || {
+            if let Some(edited_profile) = edited.borrow().as_ref() {
+                check_profile(&app, edited_profile);
+            }
+        }
+    });
+    handler.on_saved({
+        let app = app.as_weak();
+        move || {
+            if let Some(edited_profile) = edited.borrow_mut().as_mut() {
+                save_profile(&app, edited_profile);
+            }
+        }
+    });
+}
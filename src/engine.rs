@@ -0,0 +1,1725 @@
+use crate::lemmy::api::Api;
+use crate::lemmy::api::ApiError;
+use crate::lemmy::api::SiteProfile;
+use crate::lemmy::compat::Backend;
+use crate::lemmy::rate_limit::RateLimiter;
+use crate::lemmy::typecast::FromAPI;
+use crate::journal::ChangeKind;
+use crate::journal::ChangeState;
+use crate::journal::UploadJournal;
+use crate::migrations;
+use crate::opml;
+use crate::reddit;
+use crate::profile;
+use crate::storage;
+use crate::validation;
+
+use lemmy_api_common::lemmy_db_schema::newtypes;
+use lemmy_api_common::site;
+use futures::stream;
+use futures::StreamExt;
+use url::Url;
+
+use std::future::Future;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::path::Path;
+use std::path::PathBuf;
+use std::fs::File;
+use std::io::prelude::*;
+
+const SAVED_POSTS_PAGE_LIMIT: i64 = 50;
+const API_PROFILE_DOWNLOAD_FILENAME: &str = "api_profile_download.json";
+const API_PROFILE_UPLOAD_FILENAME: &str = "api_profile_upload.json";
+const REDACTED_API_FIELDS: [&str; 7] = ["jwt", "auth", "email", "password_encrypted", "totp_2fa_secret", "totp_2fa_url", "private_key"];
+const REDACTED_VALUE: &str = "REDACTED";
+const AVATAR_FILENAME: &str = "profile_avatar";
+const BANNER_FILENAME: &str = "profile_banner";
+const DOWNLOAD_CANCELLED: &str = "Download cancelled. No profile was saved.";
+const UPLOAD_CANCELLED: &str = "Upload cancelled. No changes were made.";
+const UNDO_CANCELLED: &str = "Undo cancelled. No changes were made.";
+
+/// Which parts of a profile are applied on upload, as toggled on the Settings page.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct GlobalSettings {
+    pub upload_profile_settings: bool,
+    pub upload_community_subs: bool,
+    pub upload_community_blocks: bool,
+    pub upload_user_blocks: bool,
+    pub upload_user_saved_posts: bool,
+    pub sync_removals: bool,
+    pub confirm_uploads: bool,
+    pub write_api_profiles: bool,
+    #[serde(default)]
+    pub upload_display_name: bool,
+    #[serde(default)]
+    pub upload_bio: bool,
+    #[serde(default)]
+    pub upload_matrix_user_id: bool,
+    #[serde(default = "upload_by_default")]
+    pub upload_instance_blocks: bool,
+}
+
+// Settings saved before an upload option existed keep uploading everything they did, plus the new option
+fn upload_by_default() -> bool {
+    return true;
+}
+
+impl Default for GlobalSettings {
+    fn default() -> Self {
+        GlobalSettings {
+            upload_profile_settings: true,
+            upload_community_subs: true,
+            upload_community_blocks: true,
+            upload_user_blocks: true,
+            upload_user_saved_posts: false,
+            sync_removals: false,
+            confirm_uploads: true,
+            write_api_profiles: false,
+            upload_display_name: false,
+            upload_bio: false,
+            upload_matrix_user_id: false,
+            upload_instance_blocks: true,
+        }
+    }
+}
+
+/// Account credentials and upload options for a single download or upload.
+#[derive(Debug, Clone)]
+pub struct ProcessingInstruction {
+    pub instance: String,
+    pub username: String,
+    pub password: String,
+    pub two_factor_token: String,
+    pub global_settings: GlobalSettings,
+    /// Stored profile to upload, see `storage::list_profiles`. Empty picks the only stored profile.
+    pub profile_name: String,
+}
+
+/// Lets the caller stop a download or upload once the requests in progress finish.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    pub fn new() -> Cancellation {
+        return Cancellation::default();
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Clears an earlier cancellation, so the same signal can be used for the next operation.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+
+    fn check(&self, message: &str) -> Result<(), String> {
+        if self.is_cancelled() {
+            return Err(message.to_string());
+        }
+        return Ok(());
+    }
+}
+
+struct UploadPlan {
+    api: Api,
+    jwt_token: String,
+    site_response: site::GetSiteResponse,
+    /// The account as it is before the upload, without its saved posts
+    account_profile: profile::ProfileConfiguration,
+    profile_name: String,
+    original_profile: profile::ProfileConfiguration,
+    profile_directory: PathBuf,
+    target_directory: PathBuf,
+}
+
+fn evaluate_two_factor_token(token: &String) -> Result<Option<String>, &str> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+
+    if token.chars().count() != 6 {
+        return Err("2FA Token should be 6 characters")
+    }
+
+    match token.parse::<u32>() {
+        Ok(_) => Ok(Some(token.clone())),
+        Err(_) => Err("2FA Token should be a number"),
+    }
+}
+
+/// Writes the profile to the directory using the latest profile version name.
+pub fn write_profile(directory: &Path, profile_local: &profile::ProfileConfiguration, mut logger: impl FnMut(String)) -> Result<(), String> {
+    let profile_filename = migrations::profile_migrate::get_latest_profile_name();
+    let path = directory.join(profile_filename.as_str());
+    let mut file = match File::create(&path) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("ERROR: Cannot write file - {}: {}", path.display(), e)),
+    };
+
+    let json_string = serde_json::to_string_pretty(&profile_local);
+    match file.write_all(json_string.unwrap().as_bytes()) {
+        Ok(_) => {
+            logger(format!("Wrote Profile to: {}", path.display()));
+            return Ok(());
+        },
+        Err(e) => {
+            return Err(format!("ERROR: Cannot write file - {}: {}", path.display(), e));
+        }
+    }
+}
+
+fn redact_api_profile(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, field_value) in map.iter_mut() {
+                if REDACTED_API_FIELDS.contains(&key.as_str()) {
+                    if !field_value.is_null() {
+                        *field_value = serde_json::Value::String(REDACTED_VALUE.to_string());
+                    }
+                } else {
+                    redact_api_profile(field_value);
+                }
+            }
+        },
+        serde_json::Value::Array(values) => {
+            for field_value in values.iter_mut() {
+                redact_api_profile(field_value);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Writes the raw API response for an account, with secrets redacted, to help debug profile conversion.
+pub fn write_api_profile(site_response: &site::GetSiteResponse, path: &Path, mut logger: impl FnMut(String)) {
+    let mut api_profile = match serde_json::to_value(site_response) {
+        Ok(value) => value,
+        Err(e) => {
+            logger(format!("ERROR: Cannot convert API Profile to JSON - {}", e));
+            return;
+        },
+    };
+    redact_api_profile(&mut api_profile);
+
+    let mut file = match File::create(path) {
+        Ok(file) => file,
+        Err(e) => {
+            logger(format!("ERROR: Cannot write file - {}: {}", path.display(), e));
+            return;
+        }
+    };
+
+    let json_string = serde_json::to_string_pretty(&api_profile);
+    match file.write_all(json_string.unwrap().as_bytes()) {
+        Ok(_) => logger(format!("Wrote API Profile to: {}", path.display())),
+        Err(e) => logger(format!("ERROR: Cannot write file - {}: {}", path.display(), e)),
+    }
+}
+
+/// Accepts an instance with or without the scheme, e.g. lemmy.ml or https://lemmy.ml
+fn parse_instance_url(instance: &str) -> Result<Url, String> {
+    let mut instance = instance.to_string();
+    if !instance.starts_with("http") {
+        instance.insert_str(0, "https://");
+    }
+
+    match Url::parse(instance.as_str()) {
+        Ok(url) => return Ok(url),
+        Err(_) => return Err("ERROR: Invalid Instance URL".to_string()),
+    }
+}
+
+/// Connects to the instance in the instruction and logs in, returning the API and JWT token.
+pub async fn login(processing_instruction: &ProcessingInstruction, mut logger: impl FnMut(String)) -> Result<(Api, String), String> {
+    // Fetch data from UI
+    let username = processing_instruction.username.clone();
+    let password = processing_instruction.password.clone();
+    let two_factor_token = match evaluate_two_factor_token(&processing_instruction.two_factor_token) {
+        Ok(token) => token,
+        Err(e) => return Err(format!("ERROR: Invalid 2FA Token - {}", e)),
+    };
+
+    let instance_url = parse_instance_url(&processing_instruction.instance)?;
+
+    let api = match Api::new(instance_url).await {
+        Ok(api) => api,
+        Err(e) => return Err(format!("ERROR: Invalid Instance URL (or instance is down) - {e}")),
+    };
+    logger(format!("Using the {} API", api.backend()));
+
+    // Login
+    logger(format!("Logging in as {}", username));
+    let jwt_token = match api.login(&username, &password, two_factor_token).await {
+        Ok(jwt_token) => jwt_token,
+        Err(e) => return Err(format!("ERROR: Failed Login - {}", e)),
+    };
+    logger("Login Successful.".to_string());
+
+    return Ok((api, jwt_token));
+}
+
+/// Downloads the account's profile and stores it under the account's profile name.
+///
+/// Once `cancellation` is triggered, the download stops after the request in progress without saving anything.
+pub async fn process_download(processing_instruction: ProcessingInstruction,
+    mut logger: impl FnMut(String),
+    cancellation: &Cancellation) -> Result<(), String> {
+
+    let (api, jwt_token) = login(&processing_instruction, &mut logger).await?;
+    cancellation.check(DOWNLOAD_CANCELLED)?;
+
+    // Fetch Profile
+    let site_profile = match api.fetch_profile(&jwt_token).await {
+        Ok(site_profile) => site_profile,
+        Err(e) => return Err(format!("ERROR: Failed to fetch Profile - {}", e)),
+    };
+    let profile_settings = &site_profile.site_response;
+    logger("Profile retrieved!".to_string());
+    cancellation.check(DOWNLOAD_CANCELLED)?;
+
+    let person = &profile_settings.my_user.as_ref().unwrap().local_user_view.person;
+    let profile_name = storage::profile_name(&person.name, api.instance());
+    let profile_directory = storage::create_profile_directory(&profile_name)?;
+
+    if processing_instruction.global_settings.write_api_profiles {
+        write_api_profile(profile_settings, &profile_directory.join(API_PROFILE_DOWNLOAD_FILENAME), &mut logger);
+    }
+
+    // Convert Profile
+    let mut profile_local = construct_account_profile(&site_profile);
+    profile_local.metadata = Some(profile::ProfileMetadata::new(&person.actor_id.to_string(),
+                                                                api.instance().as_str(),
+                                                                &profile_settings.version));
+
+    // Fetch Saved Posts
+    profile_local.saved_posts = fetch_saved_posts(&api, &jwt_token, person.id).await?;
+    logger(format!("{} saved posts retrieved!", profile_local.saved_posts.len()));
+    cancellation.check(DOWNLOAD_CANCELLED)?;
+
+    // Keep local copies of the avatar and banner in case the original instance goes away
+    let images = [(&profile_local.profile_settings.avatar, AVATAR_FILENAME), (&profile_local.profile_settings.banner, BANNER_FILENAME)];
+    for (image_url, base_filename) in images {
+        if let Some(image_url) = image_url {
+            cancellation.check(DOWNLOAD_CANCELLED)?;
+            match download_image(&api, image_url, &profile_directory, base_filename).await {
+                Ok(path) => logger(format!("Wrote Image to: {}", path.display())),
+                Err(e) => logger(e),
+            }
+        }
+    }
+
+    // Write to File
+    cancellation.check(DOWNLOAD_CANCELLED)?;
+    write_profile(&profile_directory, &profile_local, &mut logger)?;
+    logger(format!("Saved Profile as {}", profile_name));
+
+    return Ok(());
+}
+
+/// Converts the account's site response to a profile, adding the settings and blocks only Lemmy 0.19 has.
+///
+/// Saved posts are left out, see fetch_saved_posts.
+fn construct_account_profile(site_profile: &SiteProfile) -> profile::ProfileConfiguration {
+    let mut account_profile = FromAPI::construct_profile(&site_profile.site_response);
+    FromAPI::add_profile_additions(&mut account_profile, &site_profile.profile_additions);
+
+    return account_profile;
+}
+
+async fn fetch_saved_posts(api: &Api, jwt_token: &str, person_id: newtypes::PersonId) -> Result<Vec<String>, String> {
+    let mut saved_posts: Vec<String> = vec![];
+    let mut page = 1;
+
+    loop {
+        let saved_posts_response = match api.fetch_saved_posts(jwt_token, person_id, page, SAVED_POSTS_PAGE_LIMIT).await {
+            Ok(response) => response,
+            Err(e) => return Err(format!("ERROR: Failed to fetch Saved Posts - {}", e)),
+        };
+
+        saved_posts.append(&mut FromAPI::construct_saved_posts(&saved_posts_response.posts));
+        if (saved_posts_response.posts.len() as i64) < SAVED_POSTS_PAGE_LIMIT {
+            break;
+        }
+        page += 1;
+    }
+
+    return Ok(saved_posts);
+}
+
+fn image_filename(base_filename: &str, image_url: &str) -> String {
+    // Keep the original extension (if any) so the local copy opens in an image viewer
+    let last_segment = image_url.rsplit('/').next().unwrap_or("");
+    match last_segment.rsplit_once('.') {
+        Some((_, extension)) if !extension.is_empty() => format!("{}.{}", base_filename, extension),
+        _ => base_filename.to_string(),
+    }
+}
+
+async fn download_image(api: &Api, image_url: &str, directory: &Path, base_filename: &str) -> Result<PathBuf, String> {
+    let image = match api.fetch_image(image_url).await {
+        Ok(image) => image,
+        Err(e) => return Err(format!("ERROR: Cannot download image {} - {}", image_url, e)),
+    };
+
+    let path = directory.join(image_filename(base_filename, image_url));
+    match std::fs::write(&path, image) {
+        Ok(_) => return Ok(path),
+        Err(e) => return Err(format!("ERROR: Cannot write file - {}: {}", path.display(), e)),
+    }
+}
+
+/// Uploads the original image to the target instance, returning its new URL.
+///
+/// The local copy written on download is preferred over the original URL, and nothing is uploaded
+/// if the account's current image is already identical.
+async fn migrate_image(api: &Api,
+    jwt_token: &str,
+    original_url: &str,
+    current_url: Option<String>,
+    directory: &Path,
+    base_filename: &str) -> Result<String, String> {
+
+    let filename = image_filename(base_filename, original_url);
+    let image = match std::fs::read(directory.join(filename.as_str())) {
+        Ok(image) => image,
+        Err(_) => match api.fetch_image(original_url).await {
+            Ok(image) => image,
+            Err(e) => return Err(format!("Cannot download image {}, got exception {}", original_url, e)),
+        },
+    };
+
+    if let Some(current_url) = current_url {
+        if api.fetch_image(&current_url).await.is_ok_and(|current_image| current_image == image) {
+            return Ok(current_url);
+        }
+    }
+
+    let upload_response = match api.upload_image(jwt_token, &filename, image).await {
+        Ok(response) => response,
+        Err(e) => return Err(format!("Cannot upload image {}, got exception {}", original_url, e)),
+    };
+
+    match upload_response.files.as_ref().and_then(|files| files.first()) {
+        Some(uploaded_file) => return Ok(api.image_url(&uploaded_file.file)),
+        None => return Err(format!("Server refused to upload image {}: {}", original_url, upload_response.msg)),
+    }
+}
+
+/// Reads the newest version of a stored profile, migrating older profile versions as needed.
+pub fn read_profile(profile_name: &str, logger: impl FnMut(String)) -> Result<profile::ProfileConfiguration, String> {
+    let directory = storage::get_profile_directory(profile_name)?;
+    return migrations::profile_migrate::read_latest_profile(&directory, logger);
+}
+
+/// Combines stored profiles into a new stored profile that can be uploaded like any other.
+///
+/// Follows, blocks and saved posts are the union of every profile, while the profile settings (and the avatar and
+/// banner) are taken from `settings_profile_name`, which must be one of them.
+pub fn process_merge(profile_names: &[String],
+    settings_profile_name: &str,
+    merged_profile_name: &str,
+    mut logger: impl FnMut(String)) -> Result<(), String> {
+
+    if profile_names.len() < 2 {
+        return Err("ERROR: Choose at least two saved profiles to merge.".to_string());
+    }
+    let settings_index = match profile_names.iter().position(|profile_name| profile_name == settings_profile_name) {
+        Some(settings_index) => settings_index,
+        None => return Err(format!("ERROR: The profile settings must come from one of the merged profiles, not {}", settings_profile_name)),
+    };
+    let merged_profile_name = merged_profile_name.trim();
+    if merged_profile_name == storage::WORKING_DIRECTORY_PROFILE {
+        return Err(format!("ERROR: Invalid name for the merged profile - {}", merged_profile_name));
+    }
+    storage::validate_profile_name(merged_profile_name)?;
+    if profile_names.iter().any(|profile_name| profile_name == merged_profile_name) {
+        return Err(format!("ERROR: The merged profile cannot replace {}, which is being merged", merged_profile_name));
+    }
+
+    let mut profiles: Vec<profile::ProfileConfiguration> = vec![];
+    for profile_name in profile_names {
+        profiles.push(read_profile(profile_name, &mut logger)?);
+        logger(format!("Using saved Profile {}", profile_name));
+    }
+
+    let merged_profile = profile::merge_profiles(&profiles, &profiles[settings_index].profile_settings);
+    logger(format!("Merged {} users blocked, {} communities blocked, {} instances blocked, {} communities followed and {} posts saved.",
+                   merged_profile.blocked_users.len(),
+                   merged_profile.blocked_communities.len(),
+                   merged_profile.blocked_instances.len(),
+                   merged_profile.followed_communities.len(),
+                   merged_profile.saved_posts.len()));
+    logger(format!("Profile settings come from {}", settings_profile_name));
+
+    if storage::list_profiles().iter().any(|profile_name| profile_name == merged_profile_name) {
+        logger(format!("Replacing saved Profile {}", merged_profile_name));
+    }
+    let merged_directory = storage::create_profile_directory(merged_profile_name)?;
+    write_profile(&merged_directory, &merged_profile, &mut logger)?;
+
+    // Upload looks for the images next to the profile, so bring along the local copies made on download
+    let settings_directory = storage::get_profile_directory(settings_profile_name)?;
+    let images = [
+        (&merged_profile.profile_settings.avatar, AVATAR_FILENAME),
+        (&merged_profile.profile_settings.banner, BANNER_FILENAME),
+    ];
+    for (image_url, base_filename) in images {
+        if let Some(image_url) = image_url {
+            let filename = image_filename(base_filename, image_url);
+            let image_path = settings_directory.join(filename.as_str());
+            if image_path.exists() {
+                if let Err(e) = std::fs::copy(&image_path, merged_directory.join(filename.as_str())) {
+                    logger(format!("ERROR: Cannot copy image {} - {}", image_path.display(), e));
+                }
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+// How many communities a search returns to pick a same-named one from
+const COMMUNITY_SEARCH_LIMIT: i64 = 50;
+
+/// Finds the most followed community on the instance named exactly like the subreddit, ignoring case.
+async fn search_community(api: &Api, subreddit: &str) -> Result<Option<String>, ApiError> {
+    let search_response = api.search_communities(subreddit, COMMUNITY_SEARCH_LIMIT).await?;
+    let community_view = search_response.communities.iter()
+        .filter(|community_view| community_view.community.name.eq_ignore_ascii_case(subreddit))
+        .max_by_key(|community_view| community_view.counts.subscribers);
+
+    return Ok(community_view.map(FromAPI::construct_community_name));
+}
+
+/// Adds follows for the subreddits in a Reddit export to a stored profile.
+///
+/// Subreddits are matched using the mapping file, if given, then by searching `search_instance`, if given, for a
+/// community with the same name. Subreddits without a match are listed at the end.
+pub async fn process_reddit_import(subreddits_path: &Path,
+    mapping_path: Option<&Path>,
+    search_instance: Option<&str>,
+    profile_name: &str,
+    mut logger: impl FnMut(String)) -> Result<(), String> {
+
+    let profile_name = storage::resolve_profile_name(profile_name)?;
+    let mut profile_local = read_profile(&profile_name, &mut logger)?;
+
+    let subreddits = reddit::read_subscribed_subreddits(subreddits_path)?;
+    logger(format!("Read {} subreddit(s) from {}", subreddits.len(), subreddits_path.display()));
+
+    let mapping = match mapping_path {
+        Some(mapping_path) => reddit::read_community_mapping(mapping_path)?,
+        None => vec![],
+    };
+    let mut reddit_import = reddit::match_subreddits(&subreddits, &mapping);
+
+    if let Some(search_instance) = search_instance {
+        if !reddit_import.unmatched.is_empty() {
+            let api = match Api::new(parse_instance_url(search_instance)?).await {
+                Ok(api) => api,
+                Err(e) => return Err(format!("ERROR: Invalid Instance URL (or instance is down) - {e}")),
+            };
+            logger(format!("Searching {} for {} subreddit(s)...", api.instance(), reddit_import.unmatched.len()));
+
+            let mut unmatched: Vec<String> = vec![];
+            for subreddit in reddit_import.unmatched {
+                match search_community(&api, &subreddit).await {
+                    Ok(Some(community)) => reddit_import.matched.push((subreddit, community)),
+                    Ok(None) => unmatched.push(subreddit),
+                    Err(e) => {
+                        logger(format!("Cannot search for r/{}, got exception {}", subreddit, e));
+                        unmatched.push(subreddit);
+                    },
+                }
+            }
+            reddit_import.unmatched = unmatched;
+        }
+    }
+
+    let mut new_follows = 0;
+    for community in reddit_import.communities() {
+        if !profile_local.followed_communities.contains(&community) {
+            profile_local.followed_communities.push(community);
+            new_follows += 1;
+        }
+    }
+
+    let mut description: Vec<String> = vec![];
+    let matched: Vec<String> = reddit_import.matched.iter()
+        .map(|(subreddit, community)| format!("r/{} -> {}", subreddit, community))
+        .collect();
+    let unmatched: Vec<String> = reddit_import.unmatched.iter().map(|subreddit| format!("r/{}", subreddit)).collect();
+    describe_change_list(&mut description, "Matched subreddits", &matched);
+    describe_change_list(&mut description, "No community found for", &unmatched);
+    for line in description {
+        logger(line);
+    }
+
+    write_profile(&storage::get_profile_directory(&profile_name)?, &profile_local, &mut logger)?;
+    logger(format!("Added {} new follow(s) to Profile {}", new_follows, profile_name));
+
+    return Ok(());
+}
+
+/// Writes the stored profile's followed communities to an OPML feed list that RSS readers can import.
+pub fn process_opml_export(profile_name: &str, opml_path: &Path, mut logger: impl FnMut(String)) -> Result<(), String> {
+    let profile_name = storage::resolve_profile_name(profile_name)?;
+    let profile_local = read_profile(&profile_name, &mut logger)?;
+
+    let opml_export = opml::export_opml(&format!("Lemmy communities followed by {}", profile_name), &profile_local.followed_communities);
+
+    let mut description: Vec<String> = vec![];
+    describe_change_list(&mut description, "Skipped entries that are not communities", &opml_export.skipped);
+    for line in description {
+        logger(line);
+    }
+
+    match std::fs::write(opml_path, opml_export.opml) {
+        Ok(_) => logger(format!("Wrote {} community feed(s) to: {}", opml_export.exported.len(), opml_path.display())),
+        Err(e) => return Err(format!("ERROR: Cannot write file - {}: {}", opml_path.display(), e)),
+    }
+
+    return Ok(());
+}
+
+/// Writes a stored profile as an older profile version, for older LASIM versions, listing every field left out.
+pub fn process_profile_export(profile_name: &str, profile_version: u16, output_directory: &Path, mut logger: impl FnMut(String)) -> Result<(), String> {
+    let profile_name = storage::resolve_profile_name(profile_name)?;
+    let profile_local = read_profile(&profile_name, &mut logger)?;
+
+    let profile_downgrade = migrations::profile_migrate::downgrade_profile(&profile_local, profile_version)?;
+    if profile_downgrade.lost_fields.is_empty() {
+        logger(format!("Profile version {} holds everything in {}.", profile_version, profile_name));
+    } else {
+        logger(format!("Profile version {} cannot hold these fields of {}, they are left out:", profile_version, profile_name));
+        for lost_field in &profile_downgrade.lost_fields {
+            logger(format!("    {}", lost_field));
+        }
+    }
+
+    if let Err(e) = std::fs::create_dir_all(output_directory) {
+        return Err(format!("ERROR: Cannot create directory - {}: {}", output_directory.display(), e));
+    }
+    let path = output_directory.join(migrations::profile_migrate::get_profile_name(profile_version));
+    let json_string = serde_json::to_string_pretty(&profile_downgrade.profile_json).unwrap();
+    match std::fs::write(&path, json_string) {
+        Ok(_) => logger(format!("Wrote Profile to: {}", path.display())),
+        Err(e) => return Err(format!("ERROR: Cannot write file - {}: {}", path.display(), e)),
+    }
+
+    return Ok(());
+}
+
+/// Adds follows to a stored profile for every Lemmy community feed in an OPML feed list.
+pub fn process_opml_import(opml_path: &Path, profile_name: &str, mut logger: impl FnMut(String)) -> Result<(), String> {
+    let profile_name = storage::resolve_profile_name(profile_name)?;
+    let mut profile_local = read_profile(&profile_name, &mut logger)?;
+
+    let opml = match std::fs::read_to_string(opml_path) {
+        Ok(opml) => opml,
+        Err(e) => return Err(format!("ERROR: Cannot read file - {}: {}", opml_path.display(), e)),
+    };
+    let opml_import = opml::import_opml(&opml);
+
+    let mut new_follows: Vec<String> = vec![];
+    for community in opml_import.communities {
+        if !profile_local.followed_communities.contains(&community) {
+            profile_local.followed_communities.push(community.clone());
+            new_follows.push(community);
+        }
+    }
+
+    let mut description: Vec<String> = vec![];
+    describe_change_list(&mut description, "Communities to follow", &new_follows);
+    describe_change_list(&mut description, "Skipped feeds that are not Lemmy communities", &opml_import.skipped);
+    for line in description {
+        logger(line);
+    }
+
+    write_profile(&storage::get_profile_directory(&profile_name)?, &profile_local, &mut logger)?;
+    logger(format!("Added {} new follow(s) to Profile {}", new_follows.len(), profile_name));
+
+    return Ok(());
+}
+
+/// What an upload did with each change it was asked to make, e.g. "follow community rust@lemmy.ml".
+#[derive(Debug, Default)]
+pub struct UploadReport {
+    pub applied: Vec<String>,
+    pub failed: Vec<String>,
+    /// Failed changes where the instance kept rate limiting, timing out or erroring until the retries ran out
+    pub retries_exhausted: Vec<String>,
+    /// Changes that were never attempted because the upload was cancelled
+    pub not_applied: Vec<String>,
+}
+
+impl UploadReport {
+    fn record_failure(&mut self, change: String, failure: &EntryFailure) {
+        if failure.retries_exhausted {
+            self.retries_exhausted.push(change.clone());
+        }
+        self.failed.push(change);
+    }
+}
+
+impl std::ops::AddAssign for UploadReport {
+    fn add_assign(&mut self, mut other: UploadReport) {
+        self.applied.append(&mut other.applied);
+        self.failed.append(&mut other.failed);
+        self.retries_exhausted.append(&mut other.retries_exhausted);
+        self.not_applied.append(&mut other.not_applied);
+    }
+}
+
+/// Why a single entry could not be changed.
+struct EntryFailure {
+    message: String,
+    retries_exhausted: bool,
+}
+
+impl EntryFailure {
+    fn new(message: String) -> EntryFailure {
+        return EntryFailure {
+            message: message,
+            retries_exhausted: false,
+        };
+    }
+
+    fn from_api_error(message: String, error: &ApiError) -> EntryFailure {
+        return EntryFailure {
+            message: message,
+            retries_exhausted: error.is_retries_exhausted(),
+        };
+    }
+}
+
+/// Applies `apply` to every entry, running as many at once as the instance's rate limit allows.
+///
+/// Failures are logged as they come in, so the log order may differ from the entry order. Once `cancellation`
+/// is triggered, entries not yet started are skipped. Each result is recorded in the journal, if there is one, which
+/// is written in full before returning.
+async fn apply_entries<'a, Apply, Applying>(api: &Api,
+    cancellation: &Cancellation,
+    mut upload_journal: Option<&mut UploadJournal>,
+    mut logger: impl FnMut(String),
+    change: &str,
+    kind: ChangeKind,
+    entries: &'a [String],
+    apply: Apply) -> UploadReport
+where
+    Apply: Fn(&'a String) -> Applying,
+    Applying: Future<Output = Result<(), EntryFailure>>,
+{
+    let mut report = UploadReport::default();
+    let mut results = stream::iter(entries)
+        .map(|entry| {
+            let applying = apply(entry);
+            async move {
+                if cancellation.is_cancelled() {
+                    return (entry, None);
+                }
+                return (entry, Some(applying.await));
+            }
+        })
+        .buffer_unordered(api.max_concurrent_requests());
+
+    while let Some((entry, result)) = results.next().await {
+        let entry_change = format!("{} {}", change, entry);
+        let state = match result {
+            None => {
+                report.not_applied.push(entry_change);
+                continue;
+            },
+            Some(Ok(())) => {
+                report.applied.push(entry_change);
+                ChangeState::Done
+            },
+            Some(Err(failure)) => {
+                logger(failure.message.clone());
+                report.record_failure(entry_change, &failure);
+                ChangeState::Failed
+            },
+        };
+
+        if let Some(upload_journal) = upload_journal.as_mut() {
+            if let Err(e) = upload_journal.record(kind, entry, state) {
+                logger(e);
+            }
+        }
+    }
+
+    // Whatever is left of the last batch is written before the next step, or before cancelling
+    if let Some(upload_journal) = upload_journal.as_mut() {
+        if let Err(e) = upload_journal.flush() {
+            logger(e);
+        }
+    }
+
+    return report;
+}
+
+/// Blocks (or unblocks) each user, reporting which ones were changed.
+pub async fn block_users(api: &Api,
+    jwt_token: &str,
+    cancellation: &Cancellation,
+    upload_journal: Option<&mut UploadJournal>,
+    logger: impl FnMut(String),
+    user_list: &Vec<String>,
+    block: bool) -> UploadReport {
+
+    let block_text = if block {
+        "block"
+    } else {
+        "unblock"
+    };
+
+    let change = format!("{} user", block_text);
+    let kind = if block {
+        ChangeKind::BlockUser
+    } else {
+        ChangeKind::UnblockUser
+    };
+    return apply_entries(api, cancellation, upload_journal, logger, &change, kind, user_list, |user| async move {
+        let id = match api.fetch_user_details(jwt_token, user).await {
+            Ok(user_details) => user_details.person_view.person.id,
+            Err(e) => {
+                let message = format!("Cannot find user {} to {}, got exception {}", user, block_text, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        };
+
+        match api.block_user(jwt_token, id, block).await {
+            Ok(response) => {
+                if response.blocked != block {
+                    return Err(EntryFailure::new(format!("Server refused to {} user {}", block_text, user)));
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                let message = format!("Got exception {}ing user {}: {}", block_text, user, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        }
+    }).await;
+}
+
+/// Blocks (or unblocks) each community, reporting which ones were changed.
+pub async fn block_communities(api: &Api,
+    jwt_token: &str,
+    cancellation: &Cancellation,
+    upload_journal: Option<&mut UploadJournal>,
+    logger: impl FnMut(String),
+    community_list: &Vec<String>,
+    block: bool) -> UploadReport {
+
+    let block_text = if block {
+        "block"
+    } else {
+        "unblock"
+    };
+
+    let change = format!("{} community", block_text);
+    let kind = if block {
+        ChangeKind::BlockCommunity
+    } else {
+        ChangeKind::UnblockCommunity
+    };
+    return apply_entries(api, cancellation, upload_journal, logger, &change, kind, community_list, |community| async move {
+        let id = match api.fetch_community_by_name(jwt_token, community).await {
+            Ok(community_details) => community_details.community_view.community.id,
+            Err(e) => {
+                let message = format!("Cannot find community {} to {}, got exception {}", community, block_text, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        };
+
+        match api.block_community(jwt_token, id, block).await {
+            Ok(response) => {
+                if response.blocked != block {
+                    return Err(EntryFailure::new(format!("Server refused to {} community {}", block_text, community)));
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                let message = format!("Got exception {}ing community {}: {}", block_text, community, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        }
+    }).await;
+}
+
+/// Blocks (or unblocks) each whole instance by its host, reporting which ones were changed.
+///
+/// Only instances the account's own instance already knows about can be found, which needs Lemmy 0.19 or later.
+pub async fn block_instances(api: &Api,
+    jwt_token: &str,
+    cancellation: &Cancellation,
+    upload_journal: Option<&mut UploadJournal>,
+    mut logger: impl FnMut(String),
+    instance_list: &Vec<String>,
+    block: bool) -> UploadReport {
+
+    let block_text = if block {
+        "block"
+    } else {
+        "unblock"
+    };
+
+    // Instances cannot be looked up one by one, so they are all listed once
+    let mut linked_instances = vec![];
+    if !instance_list.is_empty() {
+        match api.fetch_linked_instances(jwt_token).await {
+            Ok(instances) => linked_instances = instances,
+            Err(e) => logger(format!("Cannot list the instances known to {}, got exception {}", api.instance(), e)),
+        }
+    }
+    let linked_instances = &linked_instances;
+
+    let change = format!("{} instance", block_text);
+    let kind = if block {
+        ChangeKind::BlockInstance
+    } else {
+        ChangeKind::UnblockInstance
+    };
+    return apply_entries(api, cancellation, upload_journal, &mut logger, &change, kind, instance_list, |instance| async move {
+        let id = match linked_instances.iter().find(|linked_instance| linked_instance.domain.eq_ignore_ascii_case(instance)) {
+            Some(linked_instance) => linked_instance.id,
+            None => return Err(EntryFailure::new(format!("Cannot find instance {} to {}", instance, block_text))),
+        };
+
+        match api.block_instance(jwt_token, id, block).await {
+            Ok(response) => {
+                if response.blocked != block {
+                    return Err(EntryFailure::new(format!("Server refused to {} instance {}", block_text, instance)));
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                let message = format!("Got exception {}ing instance {}: {}", block_text, instance, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        }
+    }).await;
+}
+
+/// Follows (or unfollows) each community, reporting which ones were changed.
+pub async fn follow_communities(api: &Api,
+    jwt_token: &str,
+    cancellation: &Cancellation,
+    upload_journal: Option<&mut UploadJournal>,
+    logger: impl FnMut(String),
+    community_list: &Vec<String>,
+    follow: bool) -> UploadReport {
+
+    let follow_text = if follow {
+        "follow"
+    } else {
+        "unfollow"
+    };
+
+    let change = format!("{} community", follow_text);
+    let kind = if follow {
+        ChangeKind::FollowCommunity
+    } else {
+        ChangeKind::UnfollowCommunity
+    };
+    return apply_entries(api, cancellation, upload_journal, logger, &change, kind, community_list, |community| async move {
+        let id = match api.fetch_community_by_name(jwt_token, community).await {
+            Ok(community_details) => community_details.community_view.community.id,
+            Err(e) => {
+                let message = format!("Cannot find community {}, got exception {}", community, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        };
+
+        match api.follow_community(jwt_token, id, follow).await {
+            Ok(response) => {
+                let not_subscribed = response.community_view.subscribed == lemmy_api_common::lemmy_db_schema::SubscribedType::NotSubscribed;
+                if follow && not_subscribed {
+                    return Err(EntryFailure::new(format!("Server refused to {} community {}", follow_text, community)));
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                let message = format!("Got exception {}ing community {}: {}", follow_text, community, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        }
+    }).await;
+}
+
+/// Saves (or unsaves) each post by its ActivityPub id, reporting which ones were changed.
+pub async fn save_posts(api: &Api,
+    jwt_token: &str,
+    cancellation: &Cancellation,
+    upload_journal: Option<&mut UploadJournal>,
+    logger: impl FnMut(String),
+    post_list: &Vec<String>,
+    save: bool) -> UploadReport {
+
+    let save_text = if save {
+        "save"
+    } else {
+        "unsave"
+    };
+
+    let change = format!("{} post", save_text);
+    let kind = if save {
+        ChangeKind::SavePost
+    } else {
+        ChangeKind::UnsavePost
+    };
+    return apply_entries(api, cancellation, upload_journal, logger, &change, kind, post_list, |post| async move {
+        let post_view = match api.resolve_post(jwt_token, post).await {
+            Ok(response) => response.post,
+            Err(e) => {
+                let message = format!("Cannot find post {} to {}, got exception {}", post, save_text, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        };
+        let id = match post_view {
+            Some(post_view) => post_view.post.id,
+            None => return Err(EntryFailure::new(format!("Cannot find post {} to {}, it is not a post", post, save_text))),
+        };
+
+        match api.save_post(jwt_token, id, save).await {
+            Ok(response) => {
+                if response.post_view.saved != save {
+                    return Err(EntryFailure::new(format!("Server refused to {} post {}", save_text, post)));
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                let message = format!("Got exception {}ing post {}: {}", save_text, post, e);
+                return Err(EntryFailure::from_api_error(message, &e));
+            },
+        }
+    }).await;
+}
+
+async fn fetch_upload_plan(processing_instruction: &ProcessingInstruction,
+    mut logger: impl FnMut(String)) -> Result<UploadPlan, String> {
+
+    // Read original profile
+    let profile_name = storage::resolve_profile_name(&processing_instruction.profile_name)?;
+    let mut original_profile = read_profile(&profile_name, &mut logger)?;
+    logger(format!("Using saved Profile {}", profile_name));
+    logger(describe_metadata(&original_profile.metadata));
+    check_profile(&profile_name, &original_profile, &mut logger)?;
+
+    let mut description: Vec<String> = vec![];
+    describe_change_list(&mut description, "Skipped malformed entries", &validation::remove_malformed_entries(&mut original_profile));
+    for line in description {
+        logger(line);
+    }
+
+    let (api, jwt_token) = login(processing_instruction, &mut logger).await?;
+
+    // Fetch New Profile
+    let site_profile = match api.fetch_profile(&jwt_token).await {
+        Ok(site_profile) => site_profile,
+        Err(e) => return Err(format!("ERROR: Failed to fetch Profile - {}", e)),
+    };
+    let account_profile = construct_account_profile(&site_profile);
+    let new_profile_api = site_profile.site_response;
+    logger("Existing Settings Downloaded.".to_string());
+
+    let person = &new_profile_api.my_user.as_ref().unwrap().local_user_view.person;
+    if original_profile.metadata.as_ref().is_some_and(|metadata| metadata.source_actor_id == person.actor_id.to_string()) {
+        logger(format!("WARNING: Profile {} was downloaded from this same account - uploading it reverts any changes made since.", profile_name));
+    }
+
+    let target_directory = storage::create_profile_directory(&storage::profile_name(&person.name, api.instance()))?;
+    if processing_instruction.global_settings.write_api_profiles {
+        write_api_profile(&new_profile_api, &target_directory.join(API_PROFILE_UPLOAD_FILENAME), &mut logger);
+    }
+
+    return Ok(UploadPlan {
+        api: api,
+        jwt_token: jwt_token,
+        site_response: new_profile_api,
+        account_profile: account_profile,
+        profile_directory: storage::get_profile_directory(&profile_name)?,
+        profile_name: profile_name,
+        original_profile: original_profile,
+        target_directory: target_directory,
+    });
+}
+
+/// Logs every problem found in a profile, failing if any of them stops it from being uploaded.
+fn check_profile(profile_name: &str, profile_local: &profile::ProfileConfiguration, mut logger: impl FnMut(String)) -> Result<validation::ValidationReport, String> {
+    let report = validation::validate_profile(profile_local);
+    for finding in &report.findings {
+        logger(finding.to_string());
+    }
+
+    if report.has_errors() {
+        return Err(format!("ERROR: Profile {} has {} error(s) and {} warning(s), fix the errors before uploading",
+                           profile_name,
+                           report.error_count(),
+                           report.warning_count()));
+    }
+
+    return Ok(report);
+}
+
+/// Checks a stored profile, or a profile file or folder by path, listing every problem found.
+pub fn process_profile_check(source: &str, mut logger: impl FnMut(String)) -> Result<(), String> {
+    // Paths are checked as they are, anything else is a stored profile name
+    let profile_name = if Path::new(source).exists() {
+        source.to_string()
+    } else {
+        storage::resolve_profile_name(source)?
+    };
+    let profile_local = read_profile_source(&profile_name, &mut logger)?;
+
+    let report = check_profile(&profile_name, &profile_local, &mut logger)?;
+    if report.findings.is_empty() {
+        logger(format!("No problems found in Profile {}", profile_name));
+    } else {
+        logger(format!("Profile {} has {} warning(s) and can be uploaded", profile_name, report.warning_count()));
+    }
+
+    return Ok(());
+}
+
+/// Describes where a profile was downloaded from, for review before it is uploaded.
+pub fn describe_metadata(metadata: &Option<profile::ProfileMetadata>) -> String {
+    match metadata {
+        Some(metadata) => return format!("Profile was downloaded from {} (Lemmy {} at {}) by LASIM {} on {}",
+                                         metadata.source_actor_id,
+                                         metadata.backend_version,
+                                         metadata.instance_url,
+                                         metadata.lasim_version,
+                                         metadata.downloaded_at),
+        None => return "Profile does not record where it was downloaded from.".to_string(),
+    }
+}
+
+/// Works out which changes the upload makes, limited to the ones the settings allow.
+async fn calculate_upload_changes(upload_plan: &UploadPlan,
+    global_settings: &GlobalSettings,
+    mut logger: impl FnMut(String)) -> Result<profile::ProfileChanges, String> {
+
+    logger("Calculating delta...".to_string());
+
+    // Convert
+    let mut new_profile = upload_plan.account_profile.clone();
+    if global_settings.upload_user_saved_posts {
+        let person_id = upload_plan.site_response.my_user.as_ref().unwrap().local_user_view.person.id;
+        new_profile.saved_posts = fetch_saved_posts(&upload_plan.api, &upload_plan.jwt_token, person_id).await?;
+    }
+
+    // Calculating Differences
+    let mut profile_changes = select_changes(profile::calculate_changes(&upload_plan.original_profile, &new_profile), global_settings);
+
+    let instance_block_changes = profile_changes.instances_to_block.len() + profile_changes.instances_to_unblock.len();
+    if upload_plan.api.backend() == Backend::Lemmy018 && instance_block_changes > 0 {
+        logger(format!("Skipping {} instance block change(s) - {} runs {}, which cannot block instances.",
+                       instance_block_changes,
+                       upload_plan.api.instance(),
+                       upload_plan.api.backend()));
+        profile_changes.instances_to_block.clear();
+        profile_changes.instances_to_unblock.clear();
+    }
+
+    return Ok(profile_changes);
+}
+
+fn select_changes(mut profile_changes: profile::ProfileChanges, global_settings: &GlobalSettings) -> profile::ProfileChanges {
+    // Drop anything the settings say will not be applied
+    if !global_settings.upload_user_blocks {
+        profile_changes.users_to_block.clear();
+        profile_changes.users_to_unblock.clear();
+    }
+    if !global_settings.upload_community_blocks {
+        profile_changes.communities_to_block.clear();
+        profile_changes.communities_to_unblock.clear();
+    }
+    if !global_settings.upload_instance_blocks {
+        profile_changes.instances_to_block.clear();
+        profile_changes.instances_to_unblock.clear();
+    }
+    if !global_settings.upload_community_subs {
+        profile_changes.communities_to_follow.clear();
+        profile_changes.communities_to_unfollow.clear();
+    }
+    if !global_settings.upload_user_saved_posts {
+        profile_changes.posts_to_save.clear();
+        profile_changes.posts_to_unsave.clear();
+    }
+    if !global_settings.upload_profile_settings {
+        profile_changes.profile_settings_changes.clear();
+    }
+
+    // Identity fields are opt-in; None leaves them unchanged, while an empty string clears them
+    let identity_fields = [
+        ("display_name", global_settings.upload_display_name, &mut profile_changes.profile_settings.display_name),
+        ("bio", global_settings.upload_bio, &mut profile_changes.profile_settings.bio),
+        ("matrix_user_id", global_settings.upload_matrix_user_id, &mut profile_changes.profile_settings.matrix_user_id),
+    ];
+    for (name, upload_field, value) in identity_fields {
+        if upload_field {
+            *value = Some(value.take().unwrap_or_default());
+        } else {
+            *value = None;
+            profile_changes.profile_settings_changes.retain(|setting_change| setting_change.name != name);
+        }
+    }
+    if !global_settings.sync_removals {
+        profile_changes.users_to_unblock.clear();
+        profile_changes.communities_to_unblock.clear();
+        profile_changes.instances_to_unblock.clear();
+        profile_changes.communities_to_unfollow.clear();
+        profile_changes.posts_to_unsave.clear();
+    }
+
+    return profile_changes;
+}
+
+fn describe_change_list(description: &mut Vec<String>, heading: &str, entries: &[String]) {
+    if entries.is_empty() {
+        return;
+    }
+
+    description.push(format!("{} ({}):", heading, entries.len()));
+    for entry in entries {
+        description.push(format!("    {}", entry));
+    }
+}
+
+/// Lists every entry and profile setting in the changes, one line each, for review before uploading.
+pub fn describe_changes(profile_changes: &profile::ProfileChanges) -> Vec<String> {
+    let mut description: Vec<String> = vec![];
+
+    describe_change_list(&mut description, "Users to block", &profile_changes.users_to_block);
+    describe_change_list(&mut description, "Users to unblock", &profile_changes.users_to_unblock);
+    describe_change_list(&mut description, "Communities to block", &profile_changes.communities_to_block);
+    describe_change_list(&mut description, "Communities to unblock", &profile_changes.communities_to_unblock);
+    describe_change_list(&mut description, "Instances to block", &profile_changes.instances_to_block);
+    describe_change_list(&mut description, "Instances to unblock", &profile_changes.instances_to_unblock);
+    describe_change_list(&mut description, "Communities to follow", &profile_changes.communities_to_follow);
+    describe_change_list(&mut description, "Communities to unfollow", &profile_changes.communities_to_unfollow);
+    describe_change_list(&mut description, "Posts to save", &profile_changes.posts_to_save);
+    describe_change_list(&mut description, "Posts to unsave", &profile_changes.posts_to_unsave);
+
+    if !profile_changes.profile_settings_changes.is_empty() {
+        description.push(format!("Profile settings to change ({}):", profile_changes.profile_settings_changes.len()));
+        for setting_change in &profile_changes.profile_settings_changes {
+            description.push(format!("    {}: {} -> {}",
+                                     setting_change.name,
+                                     setting_change.current_value,
+                                     setting_change.new_value));
+        }
+    }
+
+    if description.is_empty() {
+        description.push("No changes needed.".to_string());
+    }
+
+    return description;
+}
+
+/// Logs every change an upload would make to the account, without making any.
+pub async fn process_diff(processing_instruction: ProcessingInstruction, mut logger: impl FnMut(String)) -> Result<(), String> {
+    let upload_plan = fetch_upload_plan(&processing_instruction, &mut logger).await?;
+    let profile_changes = calculate_upload_changes(&upload_plan, &processing_instruction.global_settings, &mut logger).await?;
+
+    for line in describe_changes(&profile_changes) {
+        logger(line);
+    }
+
+    logger("No changes were made.".to_string());
+    return Ok(());
+}
+
+/// Reads a stored profile by name, or a profile file or profile folder by path.
+pub fn read_profile_source(source: &str, logger: impl FnMut(String)) -> Result<profile::ProfileConfiguration, String> {
+    let path = Path::new(source);
+    if path.is_file() {
+        return migrations::profile_migrate::read_profile_file(path);
+    }
+    if path.is_dir() && migrations::profile_migrate::find_latest_profile_version(path).is_some() {
+        return migrations::profile_migrate::read_latest_profile(path, logger);
+    }
+
+    return read_profile(&storage::resolve_profile_name(source)?, logger);
+}
+
+/// Lists everything that differs going from one profile to another, one line each.
+///
+/// Entries only in `to_profile` are added and entries only in `from_profile` are removed, while settings show
+/// the value in `from_profile` followed by the value in `to_profile`.
+pub fn describe_differences(from_profile: &profile::ProfileConfiguration, to_profile: &profile::ProfileConfiguration) -> Vec<String> {
+    let differences = profile::calculate_changes(to_profile, from_profile);
+    let mut description: Vec<String> = vec![];
+
+    describe_change_list(&mut description, "Followed communities added", &differences.communities_to_follow);
+    describe_change_list(&mut description, "Followed communities removed", &differences.communities_to_unfollow);
+    describe_change_list(&mut description, "Blocked communities added", &differences.communities_to_block);
+    describe_change_list(&mut description, "Blocked communities removed", &differences.communities_to_unblock);
+    describe_change_list(&mut description, "Blocked instances added", &differences.instances_to_block);
+    describe_change_list(&mut description, "Blocked instances removed", &differences.instances_to_unblock);
+    describe_change_list(&mut description, "Blocked users added", &differences.users_to_block);
+    describe_change_list(&mut description, "Blocked users removed", &differences.users_to_unblock);
+    describe_change_list(&mut description, "Saved posts added", &differences.posts_to_save);
+    describe_change_list(&mut description, "Saved posts removed", &differences.posts_to_unsave);
+
+    if !differences.profile_settings_changes.is_empty() {
+        description.push(format!("Profile settings that differ ({}):", differences.profile_settings_changes.len()));
+        for setting_change in &differences.profile_settings_changes {
+            description.push(format!("    {}: {} -> {}",
+                                     setting_change.name,
+                                     setting_change.current_value,
+                                     setting_change.new_value));
+        }
+    }
+
+    if description.is_empty() {
+        description.push("The profiles are identical.".to_string());
+    }
+
+    return description;
+}
+
+/// Logs what differs between two profiles, each a stored profile name or a path, without logging in anywhere.
+pub fn process_compare(from_source: &str, to_source: &str, mut logger: impl FnMut(String)) -> Result<(), String> {
+    let from_profile = read_profile_source(from_source, &mut logger)?;
+    let to_profile = read_profile_source(to_source, &mut logger)?;
+
+    logger(format!("Comparing {} with {}", from_source, to_source));
+    for line in describe_differences(&from_profile, &to_profile) {
+        logger(line);
+    }
+
+    return Ok(());
+}
+
+/// Logs what differs between the instruction's profile and the account as it is right now, without changing either.
+pub async fn process_compare_account(processing_instruction: ProcessingInstruction, mut logger: impl FnMut(String)) -> Result<(), String> {
+    let from_name = match processing_instruction.profile_name.is_empty() {
+        true => storage::resolve_profile_name("")?,
+        false => processing_instruction.profile_name.clone(),
+    };
+    let from_profile = read_profile_source(&from_name, &mut logger)?;
+
+    let (api, jwt_token) = login(&processing_instruction, &mut logger).await?;
+    let site_profile = match api.fetch_profile(&jwt_token).await {
+        Ok(site_profile) => site_profile,
+        Err(e) => return Err(format!("ERROR: Failed to fetch Profile - {}", e)),
+    };
+    let person = &site_profile.site_response.my_user.as_ref().unwrap().local_user_view.person;
+    let mut account_profile = construct_account_profile(&site_profile);
+    account_profile.saved_posts = fetch_saved_posts(&api, &jwt_token, person.id).await?;
+    logger("Profile retrieved!".to_string());
+
+    logger(format!("Comparing {} with the account {}", from_name, storage::profile_name(&person.name, api.instance())));
+    for line in describe_differences(&from_profile, &account_profile) {
+        logger(line);
+    }
+
+    return Ok(());
+}
+
+/// Applies the saved profile to the account according to the instruction's settings.
+///
+/// When `confirm_uploads` is set, the full set of changes is logged and `confirm` decides whether to apply them.
+/// Once `cancellation` is triggered, no further changes are started and what was applied is logged.
+pub async fn process_upload(processing_instruction: ProcessingInstruction,
+    mut logger: impl FnMut(String),
+    mut confirm: impl FnMut(&profile::ProfileChanges) -> bool,
+    cancellation: &Cancellation) -> Result<(), String> {
+
+    let upload_plan = fetch_upload_plan(&processing_instruction, &mut logger).await?;
+    let global_settings = processing_instruction.global_settings;
+
+    // Resume an interrupted upload to this account, or plan a new one
+    let resumed_journal = match UploadJournal::read(&upload_plan.target_directory) {
+        Ok(Some(upload_journal)) if upload_journal.matches(&upload_plan.profile_name, &upload_plan.original_profile, &global_settings) => {
+            logger(format!("Resuming an interrupted upload: {} of {} change(s) left, {} of them failed before.",
+                           upload_journal.entries.len() - upload_journal.count(ChangeState::Done),
+                           upload_journal.entries.len(),
+                           upload_journal.count(ChangeState::Failed)));
+            Some(upload_journal)
+        },
+        Ok(Some(_)) => {
+            logger("Starting over - the interrupted upload to this account used a different profile or settings.".to_string());
+            None
+        },
+        Ok(None) => None,
+        Err(e) => {
+            logger(e);
+            None
+        },
+    };
+    let resuming = resumed_journal.is_some();
+    let mut upload_journal = match resumed_journal {
+        Some(upload_journal) => upload_journal,
+        None => {
+            let profile_changes = calculate_upload_changes(&upload_plan, &global_settings, &mut logger).await?;
+            let previous_profile_settings = match global_settings.upload_profile_settings {
+                true => Some(upload_plan.account_profile.profile_settings.clone()),
+                false => None,
+            };
+            UploadJournal::new(&upload_plan.target_directory,
+                               &upload_plan.profile_name,
+                               &upload_plan.original_profile,
+                               &global_settings,
+                               profile_changes,
+                               previous_profile_settings)
+        },
+    };
+
+    let UploadPlan {
+        api,
+        jwt_token,
+        site_response: new_profile_api,
+        profile_directory,
+        ..
+    } = upload_plan;
+    let profile_changes = upload_journal.remaining_changes();
+    let upload_profile_settings = global_settings.upload_profile_settings && upload_journal.profile_settings_remaining();
+    let mut api_calls_needed = 0u32;
+    
+    if upload_profile_settings {
+        logger("All profile settings from the original profile will be applied.".to_string());
+        api_calls_needed += 1;
+    }
+    
+    if global_settings.upload_user_blocks {
+        logger(format!("{} new users will be blocked", profile_changes.users_to_block.len()));
+        api_calls_needed += profile_changes.users_to_block.len() as u32 * 2;
+
+        if global_settings.sync_removals {
+            logger(format!("{} users will be unblocked", profile_changes.users_to_unblock.len()));
+            api_calls_needed += profile_changes.users_to_unblock.len() as u32 * 2;
+        }
+    }
+    
+    if global_settings.upload_community_blocks {
+        logger(format!("{} new communities will be blocked", profile_changes.communities_to_block.len()));
+        api_calls_needed += profile_changes.communities_to_block.len() as u32 * 2;
+
+        if global_settings.sync_removals {
+            logger(format!("{} communities will be unblocked", profile_changes.communities_to_unblock.len()));
+            api_calls_needed += profile_changes.communities_to_unblock.len() as u32 * 2;
+        }
+    }
+
+    if global_settings.upload_instance_blocks {
+        logger(format!("{} new instances will be blocked", profile_changes.instances_to_block.len()));
+        api_calls_needed += profile_changes.instances_to_block.len() as u32;
+
+        if global_settings.sync_removals {
+            logger(format!("{} instances will be unblocked", profile_changes.instances_to_unblock.len()));
+            api_calls_needed += profile_changes.instances_to_unblock.len() as u32;
+        }
+    }
+    
+    if global_settings.upload_community_subs {
+        logger(format!("{} new communities will be followed", profile_changes.communities_to_follow.len()));
+        api_calls_needed += profile_changes.communities_to_follow.len() as u32 * 2;
+
+        if global_settings.sync_removals {
+            logger(format!("{} communities will be unfollowed", profile_changes.communities_to_unfollow.len()));
+            api_calls_needed += profile_changes.communities_to_unfollow.len() as u32 * 2;
+        }
+    }
+
+    if global_settings.upload_user_saved_posts {
+        logger(format!("{} new posts will be saved", profile_changes.posts_to_save.len()));
+        api_calls_needed += profile_changes.posts_to_save.len() as u32 * 2;
+
+        if global_settings.sync_removals {
+            logger(format!("{} posts will be unsaved", profile_changes.posts_to_unsave.len()));
+            api_calls_needed += profile_changes.posts_to_unsave.len() as u32 * 2;
+        }
+    }
+
+    // Call API to actually apply changes to new account
+
+    // Account for Rate Limits - requests are spread over the instance's message allowance
+    let rate_limit = &new_profile_api.site_view.local_site_rate_limit;
+    let rate_limiter = RateLimiter::new(rate_limit.message, rate_limit.message_per_second);
+    let estimated_time_sec = rate_limiter.estimate(api_calls_needed).as_secs() as u32;
+    let api = api.with_rate_limiter(rate_limiter);
+
+    if estimated_time_sec > 60 {
+        let minutes = estimated_time_sec / 60;
+        let remaining_seconds = estimated_time_sec % 60;
+        logger(format!("Estimated Upload Time: {}m {}s", minutes, remaining_seconds));
+    } else {
+        logger(format!("Estimated Upload Time: {}s", estimated_time_sec));
+    }
+    
+    // Wait for the user to review the changes
+    if global_settings.confirm_uploads {
+        for line in describe_changes(&profile_changes) {
+            logger(line);
+        }
+
+        if !confirm(&profile_changes) {
+            // Declining a resumed upload means the next one starts over
+            if resuming {
+                if let Err(e) = upload_journal.remove() {
+                    logger(e);
+                }
+            }
+            return Err(UPLOAD_CANCELLED.to_string());
+        }
+    }
+
+    if cancellation.is_cancelled() {
+        return Err(UPLOAD_CANCELLED.to_string());
+    }
+
+    if let Err(e) = upload_journal.write() {
+        logger(e);
+    }
+
+    let mut report = UploadReport::default();
+
+    // Block / Unblock Users
+    if global_settings.upload_user_blocks {
+        report += block_users(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.users_to_block, true).await;
+        if global_settings.sync_removals {
+            report += block_users(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.users_to_unblock, false).await;
+        }
+    }
+    
+    // Block Communities
+    if global_settings.upload_community_blocks {
+        report += block_communities(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.communities_to_block, true).await;
+        if global_settings.sync_removals {
+            report += block_communities(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.communities_to_unblock, false).await;
+        }
+    }
+
+    // Block Instances
+    if global_settings.upload_instance_blocks {
+        report += block_instances(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.instances_to_block, true).await;
+        if global_settings.sync_removals {
+            report += block_instances(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.instances_to_unblock, false).await;
+        }
+    }
+    
+    // Follow Communities
+    if global_settings.upload_community_subs {
+        report += follow_communities(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.communities_to_follow, true).await;
+        if global_settings.sync_removals {
+            report += follow_communities(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.communities_to_unfollow, false).await;
+        }
+    }
+    
+    // Save Posts
+    if global_settings.upload_user_saved_posts {
+        report += save_posts(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.posts_to_save, true).await;
+        if global_settings.sync_removals {
+            report += save_posts(&api, &jwt_token, cancellation, Some(&mut upload_journal), &mut logger, &profile_changes.posts_to_unsave, false).await;
+        }
+    }
+
+    // Save profile settings
+    if upload_profile_settings && cancellation.is_cancelled() {
+        report.not_applied.push("change profile settings".to_string());
+    } else if upload_profile_settings {
+        let mut profile_settings = profile_changes.profile_settings;
+        let mut settings_state = ChangeState::Done;
+        let current_person = &new_profile_api.my_user.as_ref().unwrap().local_user_view.person;
+
+        if let Some(avatar) = &profile_settings.avatar {
+            let current_avatar = current_person.avatar.as_ref().map(|url| url.to_string());
+            match migrate_image(&api, &jwt_token, avatar, current_avatar, &profile_directory, AVATAR_FILENAME).await {
+                Ok(new_avatar) => profile_settings.avatar = Some(new_avatar),
+                Err(e) => {
+                    logger(e);
+                    profile_settings.avatar = None;
+                    report.failed.push("change avatar".to_string());
+                    settings_state = ChangeState::Failed;
+                },
+            }
+        }
+
+        if let Some(banner) = &profile_settings.banner {
+            let current_banner = current_person.banner.as_ref().map(|url| url.to_string());
+            match migrate_image(&api, &jwt_token, banner, current_banner, &profile_directory, BANNER_FILENAME).await {
+                Ok(new_banner) => profile_settings.banner = Some(new_banner),
+                Err(e) => {
+                    logger(e);
+                    profile_settings.banner = None;
+                    report.failed.push("change banner".to_string());
+                    settings_state = ChangeState::Failed;
+                },
+            }
+        }
+
+        match api.save_user_settings(&jwt_token, profile_settings).await {
+            Ok(_) => report.applied.push("change profile settings".to_string()),
+            Err(e) => {
+                let failure = EntryFailure::from_api_error(format!("Cannot save profile settings, got exception {}", e), &e);
+                logger(failure.message.clone());
+                report.record_failure("change profile settings".to_string(), &failure);
+                settings_state = ChangeState::Failed;
+            },
+        }
+
+        // A failed image means the settings are saved again, with the image, by the next upload
+        if let Err(e) = upload_journal.record_profile_settings(settings_state) {
+            logger(e);
+        }
+    }
+
+    if cancellation.is_cancelled() {
+        let mut description: Vec<String> = vec![];
+        describe_change_list(&mut description, "Applied before cancelling", &report.applied);
+        describe_change_list(&mut description, "Failed before cancelling", &report.failed);
+        describe_change_list(&mut description, "Not applied", &report.not_applied);
+        for line in description {
+            logger(line);
+        }
+        logger("Upload to this account again to resume where this upload stopped.".to_string());
+
+        let total = report.applied.len() + report.failed.len() + report.not_applied.len();
+        return Err(format!("Upload cancelled. {} of {} change(s) were applied.", report.applied.len(), total));
+    }
+
+    logger("Finished!".to_string());
+
+    if !report.retries_exhausted.is_empty() {
+        logger(format!("The instance kept rate limiting or failing on {} change(s), which were skipped:", report.retries_exhausted.len()));
+        for change in &report.retries_exhausted {
+            logger(format!("    {}", change));
+        }
+    }
+
+    // A finished upload is kept so it can be undone
+    if upload_journal.is_finished() {
+        if let Err(e) = upload_journal.finish() {
+            logger(e);
+        }
+    }
+
+    if !report.failed.is_empty() {
+        return Err(format!("ERROR: {} change(s) could not be applied - re-run to try them again", report.failed.len()));
+    }
+
+    return Ok(());
+}
+
+/// Reverts the last upload to the account, or the unfinished one, by applying the inverse of every change it made.
+///
+/// Users and communities it blocked are unblocked, follows are removed, saved posts are unsaved (and the other way
+/// around), and the profile settings the account had before are restored.
+pub async fn process_undo(processing_instruction: ProcessingInstruction,
+    mut logger: impl FnMut(String),
+    mut confirm: impl FnMut(&profile::ProfileChanges) -> bool,
+    cancellation: &Cancellation) -> Result<(), String> {
+
+    let (api, jwt_token) = login(&processing_instruction, &mut logger).await?;
+    let site_response = match api.fetch_profile_settings(&jwt_token).await {
+        Ok(site_response) => site_response,
+        Err(e) => return Err(format!("ERROR: Failed to fetch Profile - {}", e)),
+    };
+
+    // The upload is recorded with the account it was made to
+    let person_name = &site_response.my_user.as_ref().unwrap().local_user_view.person.name;
+    let target_directory = storage::get_profile_directory(&storage::profile_name(person_name, api.instance()))?;
+    let upload_journal = match UploadJournal::read_last_upload(&target_directory)? {
+        Some(upload_journal) => upload_journal,
+        None => return Err("ERROR: There is no upload to this account to undo.".to_string()),
+    };
+    logger(format!("Undoing the upload of Profile {}", upload_journal.source_profile_name));
+
+    let undo_changes = upload_journal.undo_changes();
+    let restore_profile_settings = upload_journal.profile_settings_undoable();
+
+    let rate_limit = &site_response.site_view.local_site_rate_limit;
+    let api = api.with_rate_limiter(RateLimiter::new(rate_limit.message, rate_limit.message_per_second));
+
+    if processing_instruction.global_settings.confirm_uploads {
+        for line in describe_changes(&undo_changes) {
+            logger(line);
+        }
+
+        if !confirm(&undo_changes) {
+            return Err(UNDO_CANCELLED.to_string());
+        }
+    }
+
+    if cancellation.is_cancelled() {
+        return Err(UNDO_CANCELLED.to_string());
+    }
+
+    let mut report = UploadReport::default();
+    report += block_users(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.users_to_block, true).await;
+    report += block_users(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.users_to_unblock, false).await;
+    report += block_communities(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.communities_to_block, true).await;
+    report += block_communities(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.communities_to_unblock, false).await;
+    report += block_instances(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.instances_to_block, true).await;
+    report += block_instances(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.instances_to_unblock, false).await;
+    report += follow_communities(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.communities_to_follow, true).await;
+    report += follow_communities(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.communities_to_unfollow, false).await;
+    report += save_posts(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.posts_to_save, true).await;
+    report += save_posts(&api, &jwt_token, cancellation, None, &mut logger, &undo_changes.posts_to_unsave, false).await;
+
+    if restore_profile_settings && cancellation.is_cancelled() {
+        report.not_applied.push("restore profile settings".to_string());
+    } else if restore_profile_settings {
+        // None leaves a field unchanged, so fields the account did not have are cleared with an empty string
+        let mut profile_settings = undo_changes.profile_settings;
+        for value in [
+            &mut profile_settings.avatar,
+            &mut profile_settings.banner,
+            &mut profile_settings.display_name,
+            &mut profile_settings.bio,
+            &mut profile_settings.matrix_user_id,
+        ] {
+            *value = Some(value.take().unwrap_or_default());
+        }
+
+        match api.save_user_settings(&jwt_token, profile_settings).await {
+            Ok(_) => report.applied.push("restore profile settings".to_string()),
+            Err(e) => {
+                let failure = EntryFailure::from_api_error(format!("Cannot restore profile settings, got exception {}", e), &e);
+                logger(failure.message.clone());
+                report.record_failure("restore profile settings".to_string(), &failure);
+            },
+        }
+    }
+
+    if cancellation.is_cancelled() {
+        describe_undo_report(&report, &mut logger);
+        return Err(format!("Undo cancelled. {} of {} change(s) were reverted.",
+                           report.applied.len(),
+                           report.applied.len() + report.failed.len() + report.not_applied.len()));
+    }
+
+    logger("Finished!".to_string());
+
+    if !report.failed.is_empty() {
+        // Every inverse change is safe to repeat, so the record is kept for another attempt
+        describe_undo_report(&report, &mut logger);
+        return Err(format!("ERROR: {} change(s) could not be reverted - re-run to try them again", report.failed.len()));
+    }
+
+    return upload_journal.remove();
+}
+
+fn describe_undo_report(report: &UploadReport, mut logger: impl FnMut(String)) {
+    let mut description: Vec<String> = vec![];
+    describe_change_list(&mut description, "Reverted", &report.applied);
+    describe_change_list(&mut description, "Failed", &report.failed);
+    describe_change_list(&mut description, "Not reverted", &report.not_applied);
+    for line in description {
+        logger(line);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::engine::GlobalSettings;
+use crate::profile;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Kept in the profile directory of the account being uploaded to.
+pub const JOURNAL_FILENAME: &str = "upload_journal.json";
+/// A finished journal is kept under this name so the upload can be undone.
+pub const LAST_UPLOAD_FILENAME: &str = "last_upload.json";
+const PROFILE_SETTINGS_ENTRY: &str = "profile settings";
+// Recorded changes are written in batches, every change is safe to repeat if the last batch is lost
+const WRITE_EVERY_RECORDS: usize = 50;
+const WRITE_EVERY_DURATION: Duration = Duration::from_secs(5);
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    BlockUser,
+    UnblockUser,
+    BlockCommunity,
+    UnblockCommunity,
+    BlockInstance,
+    UnblockInstance,
+    FollowCommunity,
+    UnfollowCommunity,
+    SavePost,
+    UnsavePost,
+    ProfileSettings,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeState {
+    Pending,
+    Done,
+    Failed,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub kind: ChangeKind,
+    pub entry: String,
+    pub state: ChangeState,
+}
+
+/// The changes an upload planned to make and which of them were made, so an interrupted upload can resume.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct UploadJournal {
+    pub source_profile_name: String,
+    /// The stored profile the changes were calculated from, to notice when it has been downloaded again since
+    pub source_profile: serde_json::Value,
+    pub global_settings: GlobalSettings,
+    pub changes: profile::ProfileChanges,
+    pub entries: Vec<JournalEntry>,
+    /// The account's settings before the upload changed them, for undoing it
+    #[serde(default)]
+    pub previous_profile_settings: Option<profile::ProfileSettings>,
+    #[serde(skip)]
+    path: PathBuf,
+    /// Where each change is in `entries`, a change listed twice is in there twice
+    #[serde(skip)]
+    index: HashMap<(ChangeKind, String), Vec<usize>>,
+    #[serde(skip)]
+    unwritten_records: usize,
+    #[serde(skip)]
+    last_write: Option<Instant>,
+}
+
+impl UploadJournal {
+    /// Plans every change as pending. Nothing is written until `write` is called.
+    pub fn new(directory: &Path,
+        source_profile_name: &str,
+        source_profile: &profile::ProfileConfiguration,
+        global_settings: &GlobalSettings,
+        changes: profile::ProfileChanges,
+        previous_profile_settings: Option<profile::ProfileSettings>) -> UploadJournal {
+
+        let change_lists = [
+            (ChangeKind::BlockUser, &changes.users_to_block),
+            (ChangeKind::UnblockUser, &changes.users_to_unblock),
+            (ChangeKind::BlockCommunity, &changes.communities_to_block),
+            (ChangeKind::UnblockCommunity, &changes.communities_to_unblock),
+            (ChangeKind::BlockInstance, &changes.instances_to_block),
+            (ChangeKind::UnblockInstance, &changes.instances_to_unblock),
+            (ChangeKind::FollowCommunity, &changes.communities_to_follow),
+            (ChangeKind::UnfollowCommunity, &changes.communities_to_unfollow),
+            (ChangeKind::SavePost, &changes.posts_to_save),
+            (ChangeKind::UnsavePost, &changes.posts_to_unsave),
+        ];
+
+        let mut entries: Vec<JournalEntry> = vec![];
+        for (kind, change_list) in change_lists {
+            for entry in change_list {
+                entries.push(JournalEntry {
+                    kind: kind,
+                    entry: entry.clone(),
+                    state: ChangeState::Pending,
+                });
+            }
+        }
+        if global_settings.upload_profile_settings {
+            entries.push(JournalEntry {
+                kind: ChangeKind::ProfileSettings,
+                entry: PROFILE_SETTINGS_ENTRY.to_string(),
+                state: ChangeState::Pending,
+            });
+        }
+
+        let mut journal = UploadJournal {
+            source_profile_name: source_profile_name.to_string(),
+            source_profile: serde_json::to_value(source_profile).unwrap_or_default(),
+            global_settings: global_settings.clone(),
+            changes: changes,
+            entries: entries,
+            previous_profile_settings: previous_profile_settings,
+            path: directory.join(JOURNAL_FILENAME),
+            index: HashMap::new(),
+            unwritten_records: 0,
+            last_write: None,
+        };
+        journal.build_index();
+
+        return journal;
+    }
+
+    fn build_index(&mut self) {
+        self.index.clear();
+        for (position, journal_entry) in self.entries.iter().enumerate() {
+            self.index.entry((journal_entry.kind, journal_entry.entry.clone())).or_default().push(position);
+        }
+    }
+
+    fn find(&self, kind: ChangeKind, entry: &str) -> &[usize] {
+        match self.index.get(&(kind, entry.to_string())) {
+            Some(positions) => return positions,
+            None => return &[],
+        }
+    }
+
+    fn read_file(path: PathBuf) -> Result<Option<UploadJournal>, String> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let journal_json = match std::fs::read_to_string(&path) {
+            Ok(journal_json) => journal_json,
+            Err(e) => return Err(format!("ERROR: Cannot read file - {}: {}", path.display(), e)),
+        };
+        let mut journal: UploadJournal = match serde_json::from_str(&journal_json) {
+            Ok(journal) => journal,
+            Err(e) => return Err(format!("ERROR: Failed to parse {} JSON - {}", path.display(), e)),
+        };
+        journal.path = path;
+        journal.build_index();
+
+        return Ok(Some(journal));
+    }
+
+    /// Reads the journal left in the directory by an unfinished upload, if there is one.
+    pub fn read(directory: &Path) -> Result<Option<UploadJournal>, String> {
+        return UploadJournal::read_file(directory.join(JOURNAL_FILENAME));
+    }
+
+    /// Reads the journal of the most recent upload, which is the unfinished one if there is one.
+    pub fn read_last_upload(directory: &Path) -> Result<Option<UploadJournal>, String> {
+        match UploadJournal::read(directory)? {
+            Some(journal) => return Ok(Some(journal)),
+            None => return UploadJournal::read_file(directory.join(LAST_UPLOAD_FILENAME)),
+        }
+    }
+
+    /// Whether the journal was planned from the same stored profile and upload settings.
+    pub fn matches(&self,
+        source_profile_name: &str,
+        source_profile: &profile::ProfileConfiguration,
+        global_settings: &GlobalSettings) -> bool {
+
+        // Settings that do not change what is uploaded do not matter
+        let mut planned_settings = self.global_settings.clone();
+        planned_settings.confirm_uploads = global_settings.confirm_uploads;
+        planned_settings.write_api_profiles = global_settings.write_api_profiles;
+
+        return self.source_profile_name == source_profile_name
+            && serde_json::to_value(source_profile).ok().as_ref() == Some(&self.source_profile)
+            && planned_settings == *global_settings;
+    }
+
+    pub fn count(&self, state: ChangeState) -> usize {
+        return self.entries.iter().filter(|journal_entry| journal_entry.state == state).count();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.entries.iter().all(|journal_entry| journal_entry.state == ChangeState::Done);
+    }
+
+    fn is_remaining(&self, kind: ChangeKind, entry: &str) -> bool {
+        return self.find(kind, entry).iter().any(|position| self.entries[*position].state != ChangeState::Done);
+    }
+
+    pub fn profile_settings_remaining(&self) -> bool {
+        return self.is_remaining(ChangeKind::ProfileSettings, PROFILE_SETTINGS_ENTRY);
+    }
+
+    /// The planned changes that are not done yet, including the ones that failed.
+    pub fn remaining_changes(&self) -> profile::ProfileChanges {
+        let mut changes = self.changes.clone();
+        let change_lists = [
+            (ChangeKind::BlockUser, &mut changes.users_to_block),
+            (ChangeKind::UnblockUser, &mut changes.users_to_unblock),
+            (ChangeKind::BlockCommunity, &mut changes.communities_to_block),
+            (ChangeKind::UnblockCommunity, &mut changes.communities_to_unblock),
+            (ChangeKind::BlockInstance, &mut changes.instances_to_block),
+            (ChangeKind::UnblockInstance, &mut changes.instances_to_unblock),
+            (ChangeKind::FollowCommunity, &mut changes.communities_to_follow),
+            (ChangeKind::UnfollowCommunity, &mut changes.communities_to_unfollow),
+            (ChangeKind::SavePost, &mut changes.posts_to_save),
+            (ChangeKind::UnsavePost, &mut changes.posts_to_unsave),
+        ];
+
+        for (kind, change_list) in change_lists {
+            change_list.retain(|entry| self.is_remaining(kind, entry));
+        }
+        if !self.profile_settings_remaining() {
+            changes.profile_settings_changes.clear();
+        }
+
+        return changes;
+    }
+
+    /// The changes that reverse every change this upload made.
+    pub fn undo_changes(&self) -> profile::ProfileChanges {
+        let done_entries = |kind: ChangeKind| -> Vec<String> {
+            return self.entries.iter()
+                .filter(|journal_entry| journal_entry.kind == kind && journal_entry.state == ChangeState::Done)
+                .map(|journal_entry| journal_entry.entry.clone())
+                .collect();
+        };
+
+        let mut profile_settings_changes: Vec<profile::ProfileSettingChange> = vec![];
+        if !self.profile_settings_remaining() && self.previous_profile_settings.is_some() {
+            for setting_change in &self.changes.profile_settings_changes {
+                profile_settings_changes.push(profile::ProfileSettingChange {
+                    name: setting_change.name.clone(),
+                    current_value: setting_change.new_value.clone(),
+                    new_value: setting_change.current_value.clone(),
+                });
+            }
+        }
+
+        return profile::ProfileChanges {
+            users_to_block: done_entries(ChangeKind::UnblockUser),
+            users_to_unblock: done_entries(ChangeKind::BlockUser),
+            communities_to_block: done_entries(ChangeKind::UnblockCommunity),
+            communities_to_unblock: done_entries(ChangeKind::BlockCommunity),
+            instances_to_block: done_entries(ChangeKind::UnblockInstance),
+            instances_to_unblock: done_entries(ChangeKind::BlockInstance),
+            communities_to_follow: done_entries(ChangeKind::UnfollowCommunity),
+            communities_to_unfollow: done_entries(ChangeKind::FollowCommunity),
+            posts_to_save: done_entries(ChangeKind::UnsavePost),
+            posts_to_unsave: done_entries(ChangeKind::SavePost),
+            profile_settings: self.previous_profile_settings.clone().unwrap_or_else(|| self.changes.profile_settings.clone()),
+            profile_settings_changes: profile_settings_changes,
+        };
+    }
+
+    /// Whether undoing restores the account's previous profile settings.
+    pub fn profile_settings_undoable(&self) -> bool {
+        return self.previous_profile_settings.is_some() && self.entries.iter().any(|journal_entry| {
+            journal_entry.kind == ChangeKind::ProfileSettings && journal_entry.state == ChangeState::Done
+        });
+    }
+
+    pub fn write(&mut self) -> Result<(), String> {
+        let json_string = serde_json::to_string_pretty(self).unwrap();
+        match std::fs::write(&self.path, json_string) {
+            Ok(_) => {
+                self.unwritten_records = 0;
+                self.last_write = Some(Instant::now());
+                return Ok(());
+            },
+            Err(e) => return Err(format!("ERROR: Cannot write file - {}: {}", self.path.display(), e)),
+        }
+    }
+
+    /// Writes the changes recorded since the last write, if there are any.
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.unwritten_records == 0 {
+            return Ok(());
+        }
+
+        return self.write();
+    }
+
+    /// Marks a change as done or failed. The journal is written once enough changes or time have built up.
+    pub fn record(&mut self, kind: ChangeKind, entry: &str, state: ChangeState) -> Result<(), String> {
+        for position in self.find(kind, entry).to_vec() {
+            self.entries[position].state = state;
+        }
+        self.unwritten_records += 1;
+
+        let write_due = match self.last_write {
+            Some(last_write) => last_write.elapsed() >= WRITE_EVERY_DURATION,
+            None => true,
+        };
+        if self.unwritten_records >= WRITE_EVERY_RECORDS || write_due {
+            return self.write();
+        }
+
+        return Ok(());
+    }
+
+    pub fn record_profile_settings(&mut self, state: ChangeState) -> Result<(), String> {
+        self.record(ChangeKind::ProfileSettings, PROFILE_SETTINGS_ENTRY, state)?;
+        return self.flush();
+    }
+
+    /// Keeps the finished journal as the last upload, replacing the one before it.
+    pub fn finish(mut self) -> Result<(), String> {
+        self.flush()?;
+        let last_upload_path = self.path.with_file_name(LAST_UPLOAD_FILENAME);
+        match std::fs::rename(&self.path, &last_upload_path) {
+            Ok(_) => return Ok(()),
+            Err(e) => return Err(format!("ERROR: Cannot write file - {}: {}", last_upload_path.display(), e)),
+        }
+    }
+
+    pub fn remove(self) -> Result<(), String> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        match std::fs::remove_file(&self.path) {
+            Ok(_) => return Ok(()),
+            Err(e) => return Err(format!("ERROR: Cannot remove file - {}: {}", self.path.display(), e)),
+        }
+    }
+}
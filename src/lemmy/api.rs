@@ -2,40 +2,358 @@ use lemmy_api_common::sensitive::Sensitive;
 use lemmy_api_common::person;
 use lemmy_api_common::site;
 use lemmy_api_common::community;
+use lemmy_api_common::post;
+use lemmy_api_common::lemmy_db_schema;
 use lemmy_api_common::lemmy_db_schema::newtypes;
 use reqwest::Client;
 use reqwest::ClientBuilder;
+use reqwest::header;
+use reqwest::multipart;
+use reqwest::RequestBuilder;
 use reqwest::Response;
+use reqwest::StatusCode;
 use reqwest::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
 use url::Url;
 use crate::profile;
+use crate::lemmy::compat;
+use crate::lemmy::compat::Backend;
+use crate::lemmy::rate_limit::RateLimiter;
 use crate::lemmy::typecast::ToAPI;
 
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Lemmy reports its own rate limiting as a 400 with this error, rather than a 429
+const LEMMY_RATE_LIMIT_ERROR: &str = "rate_limit_error";
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request could not be sent, or the response could not be read
+    Request(Error),
+    /// The instance answered with an error status, e.g. 404 couldnt_find_community
+    Status { status: StatusCode, error: String },
+    /// The instance kept answering with transient errors until every retry was used up
+    RetriesExhausted { attempts: u32, last_error: Box<ApiError> },
+    /// The response did not have the shape expected from the instance's Lemmy version
+    Response(serde_json::Error),
+}
+
+impl ApiError {
+    fn is_transient(&self) -> bool {
+        match self {
+            ApiError::Request(e) => return e.is_timeout() || e.is_connect(),
+            ApiError::Status { status, error } => {
+                return *status == StatusCode::TOO_MANY_REQUESTS
+                    || status.is_server_error()
+                    || error == LEMMY_RATE_LIMIT_ERROR;
+            },
+            ApiError::RetriesExhausted { .. } => return false,
+            ApiError::Response(_) => return false,
+        }
+    }
+
+    pub fn is_retries_exhausted(&self) -> bool {
+        return matches!(self, ApiError::RetriesExhausted { .. });
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Request(e) => return write!(f, "{}", e),
+            ApiError::Status { status, error } if error.is_empty() => return write!(f, "HTTP status {}", status),
+            ApiError::Status { status, error } => return write!(f, "HTTP status {}: {}", status, error),
+            ApiError::RetriesExhausted { attempts, last_error } => {
+                return write!(f, "gave up after {} attempts, last error was {}", attempts, last_error);
+            },
+            ApiError::Response(e) => return write!(f, "unexpected response - {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<Error> for ApiError {
+    fn from(e: Error) -> Self {
+        // GET requests carry the JWT in the query string, so never keep the URL around
+        return ApiError::Request(e.without_url());
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(e: serde_json::Error) -> Self {
+        return ApiError::Response(e);
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct LemmyErrorResponse {
+    error: String,
+}
+
+#[derive(serde::Deserialize)]
+struct LemmyVersionResponse {
+    version: String,
+}
+
+/// How often, and how patiently, requests that failed with a transient error are retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        };
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self.initial_backoff.saturating_mul(2u32.saturating_pow(retry));
+        return backoff.min(self.max_backoff);
+    }
+}
+
+fn parse_retry_after(response: &Response) -> Option<Duration> {
+    // Only the delay-seconds form is supported, HTTP dates fall back to the normal backoff
+    let retry_after = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+    return retry_after.trim().parse::<u64>().ok().map(Duration::from_secs);
+}
+
+// pict-rs responses are proxied by Lemmy as-is, so they have no type in lemmy_api_common
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct UploadImageFile {
+    pub file: String,
+    pub delete_token: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct UploadImageResponse {
+    pub msg: String,
+    pub files: Option<Vec<UploadImageFile>>,
+}
+
+// Lemmy 0.19 additions have no type in the 0.18 lemmy_api_common, so only the fields LASIM reads are declared
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct Instance {
+    pub id: i32,
+    pub domain: String,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct InstanceBlockView {
+    pub instance: Instance,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct LocalUserAdditions {
+    #[serde(default)]
+    pub blur_nsfw: bool,
+    #[serde(default)]
+    pub auto_expand: bool,
+    /// Unlike the 0.18 response, this keeps sort types added since, e.g. Scaled
+    #[serde(default)]
+    pub default_sort_type: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct LocalUserViewAdditions {
+    local_user: LocalUserAdditions,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct MyUserAdditions {
+    local_user_view: LocalUserViewAdditions,
+    #[serde(default)]
+    instance_blocks: Vec<InstanceBlockView>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct SiteAdditionsResponse {
+    my_user: Option<MyUserAdditions>,
+}
+
+/// The parts of an account's profile that only Lemmy 0.19 and later have.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileAdditions {
+    pub local_user: LocalUserAdditions,
+    pub instance_blocks: Vec<InstanceBlockView>,
+}
+
+/// An account's site response, with the parts of its profile the 0.18 response has no room for.
+#[derive(Debug, Clone)]
+pub struct SiteProfile {
+    pub site_response: site::GetSiteResponse,
+    pub profile_additions: ProfileAdditions,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct FederatedInstances {
+    linked: Vec<Instance>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+struct FederatedInstancesResponse {
+    federated_instances: Option<FederatedInstances>,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+struct BlockInstance {
+    instance_id: i32,
+    block: bool,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct BlockInstanceResponse {
+    pub blocked: bool,
+}
+
 pub struct Api {
     client: Client,
     instance: Url,
+    retry_policy: RetryPolicy,
+    rate_limiter: Option<RateLimiter>,
+    backend: Backend,
 }
 
 impl Api {
     pub async fn new(instance: Url) -> Result<Api, Error> {
         let mut client_builder = ClientBuilder::new();
         client_builder = client_builder.user_agent("LASIM - https://github.com/CMahaff/lasim");
+        client_builder = client_builder.timeout(REQUEST_TIMEOUT);
         let new_client = client_builder.build().unwrap();
 
-        // Check if instance is an actual Lemmy url by checking getSite
-        new_client
+        // Check if instance is an actual Lemmy url by checking getSite, whose version decides how to talk to it
+        let version_response = new_client
             .get(instance.join("/api/v3/site").unwrap())
             .send()
             .await?
-            .error_for_status()?;
+            .error_for_status()?
+            .json::<LemmyVersionResponse>()
+            .await?;
 
         return Ok(Api {
             client: new_client,
             instance,
+            retry_policy: RetryPolicy::default(),
+            rate_limiter: None,
+            backend: Backend::from_version(&version_response.version),
         });
     }
 
-    pub async fn login(&self, username: &str, password: &str, two_factor_token: Option<String>) -> Result<String, Error> {
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Api {
+        self.retry_policy = retry_policy;
+        return self;
+    }
+
+    /// Makes every request, retries included, wait for the rate limiter first.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Api {
+        self.rate_limiter = Some(rate_limiter);
+        return self;
+    }
+
+    pub fn max_concurrent_requests(&self) -> usize {
+        match &self.rate_limiter {
+            Some(rate_limiter) => return rate_limiter.max_concurrent_requests(),
+            None => return 1,
+        }
+    }
+
+    pub fn instance(&self) -> &Url {
+        return &self.instance;
+    }
+
+    pub fn backend(&self) -> Backend {
+        return self.backend;
+    }
+
+    /// Adds the JWT as a bearer header on 0.19, since 0.18 reads it from the `auth` parameter instead.
+    fn authorized(&self, request: RequestBuilder, jwt_token: &str) -> RequestBuilder {
+        match self.backend {
+            Backend::Lemmy018 => return request,
+            Backend::Lemmy019 => return request.bearer_auth(jwt_token),
+        }
+    }
+
+    fn params<T: Serialize>(&self, params: &T) -> Value {
+        // Request parameters are plain structs, which always serialize, and unset ones are left out
+        let mut params_value = serde_json::to_value(params).unwrap();
+        if let Some(params_map) = params_value.as_object_mut() {
+            params_map.retain(|_, value| !value.is_null());
+        }
+
+        match self.backend {
+            Backend::Lemmy018 => return params_value,
+            Backend::Lemmy019 => return compat::request_params(params_value),
+        }
+    }
+
+    async fn parse<T: DeserializeOwned>(&self, response: Response) -> Result<T, ApiError> {
+        let response_value = response.json::<Value>().await?;
+        return self.parse_value::<T>(response_value);
+    }
+
+    fn parse_value<T: DeserializeOwned>(&self, mut response_value: Value) -> Result<T, ApiError> {
+        if self.backend == Backend::Lemmy019 {
+            compat::adapt_response(&mut response_value);
+        }
+        return Ok(serde_json::from_value::<T>(response_value)?);
+    }
+
+    /// Sends the request, retrying rate limits, timeouts and server errors according to the retry policy.
+    ///
+    /// Requests that cannot be cloned (such as multipart uploads) are only sent once.
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ApiError> {
+        let mut request = request;
+        let mut retry = 0u32;
+
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await;
+            }
+
+            let next_request = request.try_clone();
+            let (error, retry_after) = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = parse_retry_after(&response);
+                    let error = match response.json::<LemmyErrorResponse>().await {
+                        Ok(error_response) => error_response.error,
+                        Err(_) => String::new(),
+                    };
+                    (ApiError::Status { status, error }, retry_after)
+                },
+                Err(e) => (ApiError::from(e), None),
+            };
+
+            if !error.is_transient() {
+                return Err(error);
+            }
+
+            request = match next_request {
+                Some(next_request) if retry < self.retry_policy.max_retries => next_request,
+                Some(_) => return Err(ApiError::RetriesExhausted { attempts: retry + 1, last_error: Box::new(error) }),
+                None => return Err(error),
+            };
+
+            tokio::time::sleep(retry_after.unwrap_or_else(|| self.retry_policy.backoff(retry))).await;
+            retry += 1;
+        }
+    }
+
+    pub async fn login(&self, username: &str, password: &str, two_factor_token: Option<String>) -> Result<String, ApiError> {
         let url = self.instance.join("/api/v3/user/login").unwrap();
         let params = person::Login {
             username_or_email: Sensitive::new(username.to_string()),
@@ -43,54 +361,81 @@ impl Api {
             totp_2fa_token: two_factor_token,
         };
     
-        let response: Response = self.client
+        let response: Response = self.send(self.client
             .post(url)
-            .json(&params)
-            .send()
-            .await?;
+            .json(&self.params(&params))).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<person::LoginResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json.jwt.unwrap().to_string()),
-                    Err(e) => return Err(e),
-                }
-            },
-            Err(e) => {
-                return Err(e);
-            }
-        }
+        let json = self.parse::<person::LoginResponse>(response).await?;
+        return Ok(json.jwt.unwrap().to_string());
     }
 
-    pub async fn fetch_profile_settings(&self, jwt_token: &str) -> Result<site::GetSiteResponse, Error> {
+    pub async fn fetch_profile_settings(&self, jwt_token: &str) -> Result<site::GetSiteResponse, ApiError> {
+        return Ok(self.fetch_profile(jwt_token).await?.site_response);
+    }
+
+    /// Fetches the account's site response, along with the settings and instance blocks only Lemmy 0.19 has.
+    ///
+    /// Both come from the same response. Lemmy 0.18 has none of the additions, so they are left empty.
+    pub async fn fetch_profile(&self, jwt_token: &str) -> Result<SiteProfile, ApiError> {
         let url = self.instance.join("/api/v3/site").unwrap();
         let params = site::GetSite {
             auth: Some(Sensitive::new(jwt_token.to_string())),
         };
     
-        let response: Response = self.client
-            .get(url)
-            .query(&params)
-            .send()
-            .await?;
+        let response: Response = self.send(self.authorized(self.client
+            .get(url), jwt_token)
+            .query(&self.params(&params))).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<site::GetSiteResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json),
-                    Err(e) => return Err(e.without_url()),
-                }
-            },
-            Err(e) => {
-                return Err(e.without_url());
+        let response_value = response.json::<Value>().await?;
+        let mut profile_additions = ProfileAdditions::default();
+        if self.backend == Backend::Lemmy019 {
+            // Read before adapting the response to 0.18, which replaces the sort types 0.18 does not know
+            let site_additions = serde_json::from_value::<SiteAdditionsResponse>(response_value.clone())?;
+            if let Some(my_user) = site_additions.my_user {
+                profile_additions = ProfileAdditions {
+                    local_user: my_user.local_user_view.local_user,
+                    instance_blocks: my_user.instance_blocks,
+                };
             }
         }
+
+        return Ok(SiteProfile {
+            site_response: self.parse_value::<site::GetSiteResponse>(response_value)?,
+            profile_additions: profile_additions,
+        });
+    }
+
+    /// Lists every instance this one knows about, which are the only ones that can be blocked.
+    pub async fn fetch_linked_instances(&self, jwt_token: &str) -> Result<Vec<Instance>, ApiError> {
+        let url = self.instance.join("/api/v3/federated_instances").unwrap();
+        let response: Response = self.send(self.authorized(self.client
+            .get(url), jwt_token)).await?;
+
+        let json = self.parse::<FederatedInstancesResponse>(response).await?;
+        return Ok(json.federated_instances.map(|instances| instances.linked).unwrap_or_default());
+    }
+
+    /// Blocks (or unblocks) a whole instance, which only Lemmy 0.19 and later support.
+    pub async fn block_instance(&self,
+        jwt_token: &str,
+        instance_id: i32,
+        block: bool) -> Result<BlockInstanceResponse, ApiError> {
+
+        let url = self.instance.join("/api/v3/site/block").unwrap();
+        let params = BlockInstance {
+            instance_id: instance_id,
+            block: block,
+        };
+
+        let response: Response = self.send(self.authorized(self.client
+            .post(url), jwt_token)
+            .json(&self.params(&params))).await?;
+
+        return self.parse::<BlockInstanceResponse>(response).await;
     }
 
     pub async fn fetch_community_by_name(&self, jwt_token: &str, name: &str) -> 
-        Result<community::GetCommunityResponse, Error> {
+        Result<community::GetCommunityResponse, ApiError> {
 
         let url = self.instance.join("/api/v3/community").unwrap();
         let params = community::GetCommunity {
@@ -99,30 +444,17 @@ impl Api {
             ..Default::default()
         };
     
-        let response: Response = self.client
-            .get(url)
-            .query(&params)
-            .send()
-            .await?;
+        let response: Response = self.send(self.authorized(self.client
+            .get(url), jwt_token)
+            .query(&self.params(&params))).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<community::GetCommunityResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json),
-                    Err(e) => return Err(e.without_url()),
-                }
-            },
-            Err(e) => {
-                return Err(e.without_url());
-            }
-        }
+        return self.parse::<community::GetCommunityResponse>(response).await;
     }
 
     pub async fn block_community(&self,
         jwt_token: &str,
         community_id: newtypes::CommunityId,
-        block: bool) -> Result<community::BlockCommunityResponse, Error> {
+        block: bool) -> Result<community::BlockCommunityResponse, ApiError> {
 
         let url = self.instance.join("/api/v3/community/block").unwrap();
         let params = community::BlockCommunity {
@@ -131,30 +463,17 @@ impl Api {
             auth: Sensitive::new(jwt_token.to_string()),
         };
     
-        let response: Response = self.client
-            .post(url)
-            .json(&params)
-            .send()
-            .await?;
+        let response: Response = self.send(self.authorized(self.client
+            .post(url), jwt_token)
+            .json(&self.params(&params))).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<community::BlockCommunityResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json),
-                    Err(e) => return Err(e),
-                }
-            },
-            Err(e) => {
-                return Err(e);
-            }
-        }
+        return self.parse::<community::BlockCommunityResponse>(response).await;
     }
 
     pub async fn follow_community(&self,
         jwt_token: &str,
         community_id: newtypes::CommunityId,
-        follow: bool) -> Result<community::CommunityResponse, Error> {
+        follow: bool) -> Result<community::CommunityResponse, ApiError> {
 
         let url = self.instance.join("/api/v3/community/follow").unwrap();
         let params = community::FollowCommunity {
@@ -163,28 +482,15 @@ impl Api {
             auth: Sensitive::new(jwt_token.to_string()),
         };
     
-        let response: Response = self.client
-            .post(url)
-            .json(&params)
-            .send()
-            .await?;
+        let response: Response = self.send(self.authorized(self.client
+            .post(url), jwt_token)
+            .json(&self.params(&params))).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<community::CommunityResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json),
-                    Err(e) => return Err(e),
-                }
-            },
-            Err(e) => {
-                return Err(e);
-            }
-        }
+        return self.parse::<community::CommunityResponse>(response).await;
     }
 
     pub async fn fetch_user_details(&self, jwt_token: &str, name: &str) -> 
-        Result<person::GetPersonDetailsResponse, Error> {
+        Result<person::GetPersonDetailsResponse, ApiError> {
 
         let url = self.instance.join("/api/v3/user").unwrap();
         let params = person::GetPersonDetails {
@@ -193,30 +499,17 @@ impl Api {
             ..Default::default()
         };
     
-        let response: Response = self.client
-            .get(url)
-            .query(&params)
-            .send()
-            .await?;
+        let response: Response = self.send(self.authorized(self.client
+            .get(url), jwt_token)
+            .query(&self.params(&params))).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<person::GetPersonDetailsResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json),
-                    Err(e) => return Err(e.without_url()),
-                }
-            },
-            Err(e) => {
-                return Err(e.without_url());
-            }
-        }
+        return self.parse::<person::GetPersonDetailsResponse>(response).await;
     }
 
     pub async fn block_user(&self,
         jwt_token: &str,
         person_id: newtypes::PersonId,
-        block: bool) -> Result<person::BlockPersonResponse, Error> {
+        block: bool) -> Result<person::BlockPersonResponse, ApiError> {
 
         let url = self.instance.join("/api/v3/user/block").unwrap();
         let params = person::BlockPerson {
@@ -225,51 +518,144 @@ impl Api {
             auth: Sensitive::new(jwt_token.to_string()),
         };
     
-        let response: Response = self.client
-            .post(url)
-            .json(&params)
-            .send()
-            .await?;
+        let response: Response = self.send(self.authorized(self.client
+            .post(url), jwt_token)
+            .json(&self.params(&params))).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<person::BlockPersonResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json),
-                    Err(e) => return Err(e),
-                }
-            },
-            Err(e) => {
-                return Err(e);
-            }
-        }
+        return self.parse::<person::BlockPersonResponse>(response).await;
     }
 
     pub async fn save_user_settings(&self,
         jwt_token: &str,
-        user_settings_local: profile::ProfileSettings) -> Result<person::LoginResponse, Error> {
+        user_settings_local: profile::ProfileSettings) -> Result<(), ApiError> {
 
         let url = self.instance.join("/api/v3/user/save_user_settings").unwrap();
         let mut user_settings_api = ToAPI::construct_settings(&user_settings_local);
         user_settings_api.auth = Sensitive::new(jwt_token.to_string());
+        let mut params = self.params(&user_settings_api);
+        if self.backend == Backend::Lemmy019 {
+            compat::add_settings_params(&mut params, &user_settings_local);
+        }
     
-        let response: Response = self.client
-            .put(url)
-            .json(&user_settings_api)
-            .send()
-            .await?;
+        // 0.18 answers with a new login and 0.19 with a bare success, neither of which is needed
+        self.send(self.authorized(self.client
+            .put(url), jwt_token)
+            .json(&params)).await?;
 
-        match response.error_for_status() {
-            Ok(response) => {
-                let json_result = response.json::<person::LoginResponse>().await;
-                match json_result {
-                    Ok(json) => return Ok(json),
-                    Err(e) => return Err(e),
-                }
-            },
-            Err(e) => {
-                return Err(e);
-            }
+        return Ok(());
+    }
+
+    pub async fn fetch_saved_posts(&self,
+        jwt_token: &str,
+        person_id: newtypes::PersonId,
+        page: i64,
+        limit: i64) -> Result<person::GetPersonDetailsResponse, ApiError> {
+
+        let url = self.instance.join("/api/v3/user").unwrap();
+        let params = person::GetPersonDetails {
+            person_id: Some(person_id),
+            page: Some(page),
+            limit: Some(limit),
+            saved_only: Some(true),
+            auth: Some(Sensitive::new(jwt_token.to_string())),
+            ..Default::default()
+        };
+
+        let response: Response = self.send(self.authorized(self.client
+            .get(url), jwt_token)
+            .query(&self.params(&params))).await?;
+
+        return self.parse::<person::GetPersonDetailsResponse>(response).await;
+    }
+
+    /// Searches the instance for communities matching the query, without logging in.
+    pub async fn search_communities(&self, query: &str, limit: i64) ->
+        Result<site::SearchResponse, ApiError> {
+
+        let url = self.instance.join("/api/v3/search").unwrap();
+        let params = site::Search {
+            q: query.to_string(),
+            type_: Some(lemmy_db_schema::SearchType::Communities),
+            limit: Some(limit),
+            ..Default::default()
+        };
+
+        let response: Response = self.send(self.client
+            .get(url)
+            .query(&self.params(&params))).await?;
+
+        return self.parse::<site::SearchResponse>(response).await;
+    }
+
+    pub async fn resolve_post(&self, jwt_token: &str, ap_id: &str) ->
+        Result<site::ResolveObjectResponse, ApiError> {
+
+        let url = self.instance.join("/api/v3/resolve_object").unwrap();
+        let params = site::ResolveObject {
+            q: ap_id.to_string(),
+            auth: Sensitive::new(jwt_token.to_string()),
+        };
+
+        let response: Response = self.send(self.authorized(self.client
+            .get(url), jwt_token)
+            .query(&self.params(&params))).await?;
+
+        return self.parse::<site::ResolveObjectResponse>(response).await;
+    }
+
+    pub async fn save_post(&self,
+        jwt_token: &str,
+        post_id: newtypes::PostId,
+        save: bool) -> Result<post::PostResponse, ApiError> {
+
+        let url = self.instance.join("/api/v3/post/save").unwrap();
+        let params = post::SavePost {
+            post_id,
+            save: save,
+            auth: Sensitive::new(jwt_token.to_string()),
+        };
+
+        let response: Response = self.send(self.authorized(self.client
+            .put(url), jwt_token)
+            .json(&self.params(&params))).await?;
+
+        return self.parse::<post::PostResponse>(response).await;
+    }
+
+    pub async fn fetch_image(&self, image_url: &str) -> Result<Vec<u8>, ApiError> {
+        let response: Response = self.send(self.client
+            .get(image_url)).await?;
+
+        let bytes_result = response.bytes().await;
+        match bytes_result {
+            Ok(bytes) => return Ok(bytes.to_vec()),
+            Err(e) => return Err(e.into()),
         }
     }
+
+    pub async fn upload_image(&self,
+        jwt_token: &str,
+        filename: &str,
+        image: Vec<u8>) -> Result<UploadImageResponse, ApiError> {
+
+        let url = self.instance.join("/pictrs/image").unwrap();
+        let form = multipart::Form::new()
+            .part("images[]", multipart::Part::bytes(image).file_name(filename.to_string()));
+
+        // Lemmy 0.18 only reads the JWT from a cookie for image uploads, 0.19 also takes the bearer header
+        let response: Response = self.send(self.authorized(self.client
+            .post(url), jwt_token)
+            .header(header::COOKIE, format!("jwt={}", jwt_token))
+            .multipart(form)).await?;
+
+        let json_result = response.json::<UploadImageResponse>().await;
+        match json_result {
+            Ok(json) => return Ok(json),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    pub fn image_url(&self, file: &str) -> String {
+        return self.instance.join(&format!("/pictrs/image/{}", file)).unwrap().to_string();
+    }
 }
@@ -0,0 +1,184 @@
+// Lemmy 0.19 reads the JWT from an Authorization header instead of an `auth` parameter, and changed the
+// shape of a few response fields. Rather than depend on two versions of lemmy_api_common, requests to and
+// responses from a 0.19 instance are translated here, so the rest of LASIM keeps using the 0.18 types.
+
+use crate::profile::ProfileSettings;
+
+use serde_json::Map;
+use serde_json::Value;
+
+// Sort and listing types Lemmy 0.18 knows, anything newer is replaced by Lemmy's own default
+const LEMMY_018_SORT_TYPES: [&str; 17] = [
+    "Active", "Hot", "New", "Old", "TopDay", "TopWeek", "TopMonth", "TopYear", "TopAll", "MostComments",
+    "NewComments", "TopHour", "TopSixHour", "TopTwelveHour", "TopThreeMonths", "TopSixMonths", "TopNineMonths",
+];
+// Sort types added by Lemmy 0.19
+const LEMMY_019_SORT_TYPES: [&str; 2] = ["Controversial", "Scaled"];
+const LEMMY_018_LISTING_TYPES: [&str; 3] = ["All", "Local", "Subscribed"];
+const DEFAULT_SORT_TYPE: &str = "Active";
+const DEFAULT_LISTING_TYPE: &str = "Local";
+
+/// The versions of the Lemmy API that LASIM can talk to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Lemmy 0.18, which takes the JWT as an `auth` parameter
+    Lemmy018,
+    /// Lemmy 0.19 and later, which takes the JWT as an `Authorization: Bearer` header
+    Lemmy019,
+}
+
+impl Backend {
+    /// Picks the backend for the version an instance reports from `/api/v3/site`, e.g. "0.19.3".
+    ///
+    /// Versions that cannot be read are treated as 0.18.
+    pub fn from_version(version: &str) -> Backend {
+        let numbers: Vec<u32> = version.trim()
+            .trim_start_matches('v')
+            .split('.')
+            .take(2)
+            .map(|part| part.chars().take_while(|c| c.is_ascii_digit()).collect::<String>().parse::<u32>().unwrap_or(0))
+            .collect();
+
+        let major = numbers.first().copied().unwrap_or(0);
+        let minor = numbers.get(1).copied().unwrap_or(0);
+        if major == 0 && minor < 19 {
+            return Backend::Lemmy018;
+        }
+
+        return Backend::Lemmy019;
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Lemmy018 => return write!(f, "Lemmy 0.18"),
+            Backend::Lemmy019 => return write!(f, "Lemmy 0.19"),
+        }
+    }
+}
+
+/// Whether any supported Lemmy version knows the sort type, e.g. "TopWeek".
+pub fn is_known_sort_type(sort_type: &str) -> bool {
+    return LEMMY_018_SORT_TYPES.contains(&sort_type) || LEMMY_019_SORT_TYPES.contains(&sort_type);
+}
+
+/// The sort type as Lemmy 0.18 knows it, anything newer is replaced by Lemmy's own default.
+pub fn lemmy_018_sort_type(sort_type: &str) -> &str {
+    if LEMMY_018_SORT_TYPES.contains(&sort_type) {
+        return sort_type;
+    }
+
+    return DEFAULT_SORT_TYPE;
+}
+
+/// Whether the listing type is one a profile can hold, newer ones are replaced on download.
+pub fn is_known_listing_type(listing_type: &str) -> bool {
+    return LEMMY_018_LISTING_TYPES.contains(&listing_type);
+}
+
+/// Removes the `auth` parameter from serialized 0.18 request parameters, the JWT goes in a header instead.
+pub fn request_params(mut params: Value) -> Value {
+    if let Some(params_map) = params.as_object_mut() {
+        params_map.remove("auth");
+    }
+
+    return params;
+}
+
+/// Adds the settings 0.18 has no field for to serialized `SaveUserSettings` parameters.
+pub fn add_settings_params(params: &mut Value, profile_settings: &ProfileSettings) {
+    if let Some(params_map) = params.as_object_mut() {
+        params_map.insert("blur_nsfw".to_string(), Value::Bool(profile_settings.blur_nsfw));
+        params_map.insert("auto_expand".to_string(), Value::Bool(profile_settings.auto_expand));
+        if LEMMY_019_SORT_TYPES.contains(&profile_settings.default_sort_type.as_str()) {
+            params_map.insert("default_sort_type".to_string(), Value::from(profile_settings.default_sort_type.as_str()));
+        }
+    }
+}
+
+/// Rewrites a 0.19 response in place so it deserializes as the matching 0.18 response.
+pub fn adapt_response(response: &mut Value) {
+    match response {
+        Value::String(text) => strip_timezone(text),
+        Value::Array(values) => {
+            for value in values {
+                adapt_response(value);
+            }
+        },
+        Value::Object(map) => adapt_object(map),
+        _ => {},
+    }
+}
+
+fn adapt_object(map: &mut Map<String, Value>) {
+    // Whether a person is an admin moved from the person to their local user
+    let local_admin = map.get("local_user").and_then(|local_user| local_user.get("admin")).cloned();
+    if let (Some(admin), Some(Value::Object(person))) = (local_admin, map.get_mut("person")) {
+        person.entry("admin").or_insert(admin);
+    }
+    if map.contains_key("actor_id") && map.contains_key("bot_account") {
+        map.entry("admin").or_insert(Value::Bool(false));
+    }
+
+    for (name, value) in map.iter_mut() {
+        match name.as_str() {
+            "counts" => adapt_counts(value),
+            "hot_rank" | "hot_rank_active" => {
+                // Ranks became floats, 0.18 expects whole numbers
+                if let Some(rank) = value.as_f64() {
+                    *value = Value::from(rank.round() as i64);
+                }
+            },
+            "default_sort_type" => replace_unknown(value, &LEMMY_018_SORT_TYPES, DEFAULT_SORT_TYPE),
+            "default_listing_type" | "default_post_listing_type" => {
+                replace_unknown(value, &LEMMY_018_LISTING_TYPES, DEFAULT_LISTING_TYPE);
+            },
+            _ => {},
+        }
+
+        adapt_response(value);
+    }
+}
+
+fn adapt_counts(counts: &mut Value) {
+    // Aggregates lost their own id, and person aggregates may no longer carry scores
+    if let Some(counts_map) = counts.as_object_mut() {
+        counts_map.entry("id").or_insert(Value::from(0));
+        if counts_map.contains_key("post_count") {
+            counts_map.entry("post_score").or_insert(Value::from(0));
+            counts_map.entry("comment_score").or_insert(Value::from(0));
+        }
+    }
+}
+
+fn replace_unknown(value: &mut Value, known: &[&str], default: &str) {
+    if let Some(text) = value.as_str() {
+        if !known.contains(&text) {
+            *value = Value::from(default);
+        }
+    }
+}
+
+fn strip_timezone(text: &mut String) {
+    // Timestamps became UTC, e.g. 2023-07-01T00:00:00.123456Z, while 0.18 expects 2023-07-01T00:00:00.123456
+    let naive_length = if let Some(naive) = text.strip_suffix('Z') {
+        naive.len()
+    } else if let Some(naive) = text.strip_suffix("+00:00") {
+        naive.len()
+    } else {
+        return;
+    };
+
+    let naive = &text.as_bytes()[..naive_length];
+    let is_timestamp = naive.len() >= 19
+        && naive[4] == b'-'
+        && naive[7] == b'-'
+        && naive[10] == b'T'
+        && naive[13] == b':'
+        && naive[16] == b':'
+        && naive.iter().all(|c| c.is_ascii_digit() || b"-T:.".contains(c));
+    if is_timestamp {
+        text.truncate(naive_length);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod api;
-pub mod typecast;
\ No newline at end of file
+pub mod compat;
+pub mod rate_limit;
+pub mod typecast;
@@ -0,0 +1,68 @@
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use std::time::Duration;
+
+// Never have more requests in flight than this, however generous the instance is
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token bucket mirroring a Lemmy rate limit: up to `actions` requests at once, refilled at `actions` per `per_second` seconds.
+pub struct RateLimiter {
+    capacity: f64,
+    refill_per_second: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Values get mapped as seen here: lemmy/src/api_routes_http.rs, e.g. `local_site_rate_limit.message`
+    /// and `local_site_rate_limit.message_per_second`.
+    pub fn new(actions: i32, per_second: i32) -> RateLimiter {
+        let capacity = actions.max(1) as f64;
+        let period = per_second.max(1) as f64;
+
+        return RateLimiter {
+            capacity: capacity,
+            refill_per_second: capacity / period,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        };
+    }
+
+    /// How many requests may usefully run at once without waiting on each other.
+    pub fn max_concurrent_requests(&self) -> usize {
+        return (self.capacity as usize).clamp(1, MAX_CONCURRENT_REQUESTS);
+    }
+
+    /// Roughly how long `requests` requests take, assuming the bucket starts full.
+    pub fn estimate(&self, requests: u32) -> Duration {
+        let waiting_requests = (requests as f64 - self.capacity).max(0.0);
+        return Duration::from_secs_f64(waiting_requests / self.refill_per_second);
+    }
+
+    /// Waits until the next request may be sent.
+    pub async fn acquire(&self) {
+        // The lock is held while waiting, so requests go out in the order they asked
+        let mut bucket = self.bucket.lock().await;
+
+        let now = Instant::now();
+        let refilled = now.duration_since(bucket.last_refill).as_secs_f64() * self.refill_per_second;
+        bucket.tokens = (bucket.tokens + refilled).min(self.capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens < 1.0 {
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / self.refill_per_second);
+            tokio::time::sleep(wait).await;
+            bucket.tokens = 1.0;
+            bucket.last_refill = Instant::now();
+        }
+
+        bucket.tokens -= 1.0;
+    }
+}
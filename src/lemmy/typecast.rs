@@ -1,8 +1,11 @@
 use crate::profile::ProfileSettings;
 use crate::profile::ProfileConfiguration;
+use crate::lemmy::api::ProfileAdditions;
 
 use lemmy_api_common::lemmy_db_schema::newtypes;
 use lemmy_api_common::lemmy_db_schema;
+use lemmy_api_common::lemmy_db_views::structs::PostView;
+use lemmy_api_common::lemmy_db_views_actor::structs::CommunityView;
 use lemmy_api_common::person;
 use lemmy_api_common::site;
 use lemmy_api_common::sensitive::Sensitive;
@@ -18,12 +21,12 @@ impl ToAPI {
             default_sort_type: Some(Self::cast_sort_type(&profile_settings.default_sort_type)),
             default_listing_type: Some(Self::cast_listing_type(&profile_settings.default_listing_type)),
             interface_language: Some(profile_settings.interface_language.clone()),
-            avatar: None, // TODO: Support Avatar migration
-            banner: None, // TODO: Support Banner migration
-            display_name: None, // Don't Change
+            avatar: profile_settings.avatar.clone(), // Must already be uploaded to the target instance
+            banner: profile_settings.banner.clone(), // Must already be uploaded to the target instance
+            display_name: profile_settings.display_name.clone(), // None means Don't Change
             email: None, // Don't Change
-            bio: None, // Don't Change
-            matrix_user_id: None, // Don't Change
+            bio: profile_settings.bio.clone(), // None means Don't Change
+            matrix_user_id: profile_settings.matrix_user_id.clone(), // None means Don't Change
             show_avatars: Some(profile_settings.show_avatars),
             send_notifications_to_email: Some(profile_settings.send_notifications_to_email),
             bot_account: Some(profile_settings.bot_account),
@@ -131,6 +134,11 @@ impl FromAPI {
         return new_follows;
     }
 
+    /// Names a community as name@host, e.g. rust@lemmy.ml
+    pub fn construct_community_name(community_view: &CommunityView) -> String {
+        return Self::parse_url(community_view.community.actor_id.to_string());
+    }
+
     pub fn construct_profile(original_profile: &site::GetSiteResponse) -> ProfileConfiguration {
         let my_user = &(original_profile.my_user.as_ref().unwrap());
         let local_user_view = &(my_user.local_user_view);
@@ -138,8 +146,10 @@ impl FromAPI {
         let person = &(local_user_view.person);
     
         return ProfileConfiguration {
+            metadata: None, // Added on download, see ProfileMetadata::new
             blocked_users: Self::construct_blocked_users(original_profile),
             blocked_communities: Self::construct_blocked_communities(original_profile),
+            blocked_instances: vec![], // Fetched separately, see add_profile_additions
             followed_communities: Self::construct_followed_communities(original_profile),
             profile_settings: ProfileSettings {
                 show_nsfw: local_user.show_nsfw,
@@ -157,10 +167,44 @@ impl FromAPI {
                 discussion_languages: Self::cast_language_array(&my_user.discussion_languages),
                 open_links_in_new_tab: local_user.open_links_in_new_tab,
                 infinite_scroll_enabled: local_user.infinite_scroll_enabled,
+                blur_nsfw: false, // Fetched separately, see add_profile_additions
+                auto_expand: false, // Fetched separately, see add_profile_additions
+                avatar: person.avatar.as_ref().map(|url| url.to_string()),
+                banner: person.banner.as_ref().map(|url| url.to_string()),
+                display_name: person.display_name.clone(),
+                bio: person.bio.clone(),
+                matrix_user_id: person.matrix_user_id.clone(),
             },
+            saved_posts: vec![], // Fetched separately, see construct_saved_posts
         };
     }
 
+    /// Fills in the settings and instance blocks only Lemmy 0.19 has, see Api::fetch_profile
+    pub fn add_profile_additions(profile: &mut ProfileConfiguration, profile_additions: &ProfileAdditions) {
+        let local_user = &profile_additions.local_user;
+        profile.profile_settings.blur_nsfw = local_user.blur_nsfw;
+        profile.profile_settings.auto_expand = local_user.auto_expand;
+        if let Some(default_sort_type) = &local_user.default_sort_type {
+            profile.profile_settings.default_sort_type = default_sort_type.clone();
+        }
+
+        let mut blocked_instances = vec![];
+        for instance_block_view in &profile_additions.instance_blocks {
+            blocked_instances.push(instance_block_view.instance.domain.clone());
+        }
+        profile.blocked_instances = blocked_instances;
+    }
+
+    pub fn construct_saved_posts(saved_posts: &Vec<PostView>) -> Vec<String> {
+        let mut new_saved_posts = vec![];
+
+        for saved_post_view in saved_posts {
+            new_saved_posts.push(saved_post_view.post.ap_id.to_string());
+        }
+
+        return new_saved_posts;
+    }
+
     pub fn cast_language_array(original_languages: &Vec<newtypes::LanguageId>) -> Vec<i32> {
         let mut new_languages: Vec<i32> = vec![];
         for language in original_languages {
@@ -0,0 +1,16 @@
+//! Lemmy Account Settings Instance Migrator
+//!
+//! The migration engine behind the LASIM GUI and command line, usable from other tools.
+
+#![allow(clippy::needless_return)]
+#![allow(clippy::redundant_field_names)]
+
+pub mod lemmy;
+pub mod profile;
+pub mod migrations;
+pub mod engine;
+pub mod journal;
+pub mod storage;
+pub mod reddit;
+pub mod opml;
+pub mod validation;
@@ -2,48 +2,40 @@
 #![allow(clippy::needless_return)]
 #![allow(clippy::redundant_field_names)]
 
-mod lemmy;
-mod profile;
-mod migrations;
-
-use lemmy::typecast::FromAPI;
+mod cli;
+mod editor;
+
+use lasim::engine;
+use lasim::engine::Cancellation;
+use lasim::engine::GlobalSettings;
+use lasim::engine::ProcessingInstruction;
+use lasim::storage;
+use slint::Model;
 use slint::Weak;
 use slint::SharedString;
-use url::Url;
+use slint::VecModel;
 
 use std::thread;
+use std::rc::Rc;
 use std::sync::mpsc;
 use std::sync::mpsc::{Sender, Receiver};
 use std::path::Path;
 use std::fs::File;
 use std::io::prelude::*;
-use futures::executor::block_on;
 
 slint::include_modules!();
 
 const CONFIG_FILENAME: &str = ".lasim_config.json";
 const PANIC_LOG: &str = "error.log";
-
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
-struct GlobalSettings {
-    pub upload_profile_settings: bool,
-    pub upload_community_subs: bool,
-    pub upload_community_blocks: bool,
-    pub upload_user_blocks: bool,
-    pub upload_user_saved_posts: bool,
-    pub sync_removals: bool,
-    pub confirm_uploads: bool,
-    pub write_api_profiles: bool,
-}
-
-#[derive(Debug)]
-struct ProcessingInstruction {
-    instruction_type: SharedString,
-    instance: SharedString,
-    username: SharedString,
-    password: SharedString,
-    two_factor_token: SharedString,
-    global_settings: GlobalSettings,
+const COMPARE_WITH_ACCOUNT: &str = "The account entered below";
+
+enum GuiInstruction {
+    Download(ProcessingInstruction),
+    Upload(ProcessingInstruction),
+    Undo(ProcessingInstruction),
+    /// Compares the instruction's profile with another stored profile, or with the account when there is none
+    Compare(ProcessingInstruction, Option<String>),
+    Done,
 }
 
 fn read_global_settings() -> Result<GlobalSettings, String> {
@@ -68,30 +60,77 @@ fn read_global_settings() -> Result<GlobalSettings, String> {
 }
 
 fn apply_global_settings(app: Weak<App>) {
-    let global_settings = match read_global_settings() {
-        Ok(config) => config,
-        Err(_) => {
-            GlobalSettings {
-                upload_profile_settings: true,
-                upload_community_subs: true,
-                upload_community_blocks: true,
-                upload_user_blocks: true,
-                upload_user_saved_posts: false,
-                sync_removals: false,
-                confirm_uploads: true,
-                write_api_profiles: false,
-            }
-        },
-    };
+    let global_settings = read_global_settings().unwrap_or_default();
 
     app.unwrap().set_upload_profile_settings(global_settings.upload_profile_settings);
     app.unwrap().set_upload_community_subs(global_settings.upload_community_subs);
     app.unwrap().set_upload_community_blocks(global_settings.upload_community_blocks);
+    app.unwrap().set_upload_instance_blocks(global_settings.upload_instance_blocks);
     app.unwrap().set_upload_user_blocks(global_settings.upload_user_blocks);
     app.unwrap().set_upload_user_saved_posts(global_settings.upload_user_saved_posts);
     app.unwrap().set_sync_removals(global_settings.sync_removals);
     app.unwrap().set_confirm_uploads(global_settings.confirm_uploads);
     app.unwrap().set_write_api_profiles(global_settings.write_api_profiles);
+    app.unwrap().set_upload_display_name(global_settings.upload_display_name);
+    app.unwrap().set_upload_bio(global_settings.upload_bio);
+    app.unwrap().set_upload_matrix_user_id(global_settings.upload_matrix_user_id);
+}
+
+fn apply_profile_names(app: Weak<App>) {
+    let profile_names: Vec<SharedString> = storage::list_profiles().into_iter().map(SharedString::from).collect();
+
+    // Keep the current choice if it still exists, otherwise pick the first stored profile
+    let selected_profile = app.unwrap().get_upload_selected_profile();
+    if !profile_names.contains(&selected_profile) {
+        app.unwrap().set_upload_selected_profile(profile_names.first().cloned().unwrap_or_default());
+    }
+
+    // Merging keeps the profiles that were ticked, as long as they still exist
+    let previous_sources = app.unwrap().get_merge_sources();
+    let merge_sources: Vec<MergeSource> = profile_names.iter().map(|profile_name| {
+        let selected = previous_sources.iter().any(|source| source.name == *profile_name && source.selected);
+        return MergeSource { name: profile_name.clone(), selected: selected };
+    }).collect();
+    let settings_profile = app.unwrap().get_merge_settings_profile();
+    if !profile_names.contains(&settings_profile) {
+        app.unwrap().set_merge_settings_profile(profile_names.first().cloned().unwrap_or_default());
+    }
+
+    // Comparing can be against any stored profile, or the account entered on the page
+    let mut compare_targets: Vec<SharedString> = vec![SharedString::from(COMPARE_WITH_ACCOUNT)];
+    compare_targets.extend(profile_names.iter().cloned());
+    let compare_profile = app.unwrap().get_compare_selected_profile();
+    if !profile_names.contains(&compare_profile) {
+        app.unwrap().set_compare_selected_profile(profile_names.first().cloned().unwrap_or_default());
+    }
+    let compare_target = app.unwrap().get_compare_selected_target();
+    if !compare_targets.contains(&compare_target) {
+        app.unwrap().set_compare_selected_target(SharedString::from(COMPARE_WITH_ACCOUNT));
+    }
+
+    app.unwrap().set_compare_targets(Rc::new(VecModel::from(compare_targets)).into());
+    editor::apply_profile_names(&app, &profile_names);
+    app.unwrap().set_compare_profile_names(Rc::new(VecModel::from(profile_names.clone())).into());
+    app.unwrap().set_merge_sources(Rc::new(VecModel::from(merge_sources)).into());
+    app.unwrap().set_merge_profile_names(Rc::new(VecModel::from(profile_names.clone())).into());
+    app.unwrap().set_upload_profile_names(Rc::new(VecModel::from(profile_names)).into());
+}
+
+fn collect_global_settings(app: &Weak<App>) -> GlobalSettings {
+    return GlobalSettings {
+        upload_profile_settings: app.unwrap().get_upload_profile_settings(),
+        upload_community_subs: app.unwrap().get_upload_community_subs(),
+        upload_community_blocks: app.unwrap().get_upload_community_blocks(),
+        upload_user_blocks: app.unwrap().get_upload_user_blocks(),
+        upload_user_saved_posts: app.unwrap().get_upload_user_saved_posts(),
+        sync_removals: app.unwrap().get_sync_removals(),
+        confirm_uploads: app.unwrap().get_confirm_uploads(),
+        write_api_profiles: app.unwrap().get_write_api_profiles(),
+        upload_display_name: app.unwrap().get_upload_display_name(),
+        upload_bio: app.unwrap().get_upload_bio(),
+        upload_matrix_user_id: app.unwrap().get_upload_matrix_user_id(),
+        upload_instance_blocks: app.unwrap().get_upload_instance_blocks(),
+    };
 }
 
 fn write_global_settings(global_settings: GlobalSettings) {
@@ -121,396 +160,24 @@ fn write_panic_info(info: &String) {
     file.write_all(info.as_bytes()).ok();
 }
 
-fn evaluate_two_factor_token(token: &String) -> Result<Option<String>, &str> {
-    if token.is_empty() {
-        return Ok(None);
-    }
-
-    if token.chars().count() != 6 {
-        return Err("2FA Token should be 6 characters")
-    }
-
-    match token.parse::<u32>() {
-        Ok(_) => Ok(Some(token.clone())),
-        Err(_) => Err("2FA Token should be a number"),
-    }
-}
-
-fn write_profile(profile_local: &profile::ProfileConfiguration, mut logger: impl FnMut(String)) {
-    let profile_filename = migrations::profile_migrate::get_latest_profile_name();
-    let path = Path::new(profile_filename.as_str());
-    let mut file = match File::create(path) {
-        Ok(file) => file,
-        Err(e) => {
-            logger(format!("ERROR: Cannot write file - {}: {}", path.display(), e));
-            return
-        }
-    };
-
-    let json_string = serde_json::to_string_pretty(&profile_local);
-    match file.write_all(json_string.unwrap().as_bytes()) {
-        Ok(_) => {
-            logger(format!("Wrote Profile to: {}", path.to_str().unwrap()))
-        },
-        Err(e) => {
-            logger(format!("ERROR: Cannot write file - {}: {}", path.display(), e));
-        }
-    }
-}
-
-#[tokio::main]
-async fn process_download(processing_instruction: ProcessingInstruction, mut logger: impl FnMut(String)) {
-    // Fetch data from UI
-    let mut instance = processing_instruction.instance.to_string();
-    let username = processing_instruction.username.to_string();
-    let password = processing_instruction.password.to_string();
-    let two_factor_token = match evaluate_two_factor_token(&processing_instruction.two_factor_token.to_string()) {
-        Ok(token) => token,
-        Err(e) => {
-            logger(format!("ERROR: Invalid 2FA Token - {}", e));
-            return;
-        },
-    };
-
-    if !instance.starts_with("http") {
-        instance.insert_str(0, "https://");
-    }
-    let instance_url_result = Url::parse(instance.as_str());
-    if instance_url_result.is_err() {
-        logger("ERROR: Invalid Instance URL".to_string());
-        return;
-    }
-    let instance_url = instance_url_result.unwrap();
-
-    let api = match lemmy::api::Api::new(instance_url).await {
-        Ok(api) => api,
-        Err(e) => {
-            logger(format!(
-                "ERROR: Invalid Instance URL (or instance is down) - {e}"
-            ));
-            return;
-        }
-    };
-
-    // Login
-    logger(format!("Logging in as {}", username));
-    let jwt_token_future = api.login(&username, &password, two_factor_token);
-    let jwt_token_result = block_on(jwt_token_future);
-    if jwt_token_result.is_err() {
-        logger(format!("ERROR: Failed Login - {}", jwt_token_result.unwrap_err()));
-        return;
-    }
-
-    let jwt_token = jwt_token_result.unwrap();
-    logger("Login Successful.".to_string());
-
-    // Fetch Profile
-    let profile_settings_future = api.fetch_profile_settings(&jwt_token);
-    let profile_settings_result = block_on(profile_settings_future);
-    if profile_settings_result.is_err() {
-        logger(format!("ERROR: Failed to fetch Profile - {}", profile_settings_result.unwrap_err()));
-        return;
-    }
-    let profile_settings = profile_settings_result.unwrap();
-    logger("Profile retrieved!".to_string());
-
-    // Convert Profile
-    let profile_local = FromAPI::construct_profile(&profile_settings);
-
-    // Write to File
-    write_profile(&profile_local, logger);
-    
-}
-
-fn read_profile() -> Result<profile::ProfileConfiguration, String> {
-    return migrations::profile_migrate::read_latest_profile();
-}
-
-async fn block_users(api: &lemmy::api::Api,
-    jwt_token: &str,
-    message_rate_limit: std::time::Duration,
-    mut logger: impl FnMut(String),
-    user_list: &Vec<String>,
-    block: bool) {
-
-    let block_text = if block {
-        "block"
-    } else {
-        "unblock"
-    };
-
-    for user in user_list {
-        let user_details_result = api.fetch_user_details(jwt_token, user).await;
-        thread::sleep(message_rate_limit);
-
-        if user_details_result.is_err() {
-            logger(format!("Cannot find user {} to {}, got exception {}",
-                            user,
-                            block_text,
-                            user_details_result.unwrap_err()));
-            continue;
-        }
-
-        let id = user_details_result.unwrap().person_view.person.id;
-        let block_user_result = api.block_user(jwt_token, id, block).await;
-        thread::sleep(message_rate_limit);
-
-        match block_user_result {
-            Ok(response) => {
-                if !response.blocked {
-                    format!("Server refused to {} user {}", block_text, user);
-                }
-            }
-            Err(e) => logger(format!("Got exception {}ing user {}: {}", block_text, user, e)),
-        }
-    }
-}
-
-async fn block_communities(api: &lemmy::api::Api,
-    jwt_token: &str,
-    message_rate_limit: std::time::Duration,
-    mut logger: impl FnMut(String),
-    community_list: &Vec<String>,
-    block: bool) {
-
-    let block_text = if block {
-        "block"
-    } else {
-        "unblock"
-    };
-
-    for community in community_list {
-        let community_details_result = api.fetch_community_by_name(jwt_token, community).await;
-        thread::sleep(message_rate_limit);
-
-        if community_details_result.is_err() {
-            logger(format!("Cannot find community {} to {}, got exception {}",
-                            community,
-                            block_text,
-                            community_details_result.unwrap_err()));
-            continue;
-        }
-        
-        let id = community_details_result.unwrap().community_view.community.id;
-        let block_community_result = api.block_community(jwt_token, id, block).await;
-        thread::sleep(message_rate_limit);
-
-        match block_community_result {
-            Ok(response) => {
-                if !response.blocked {
-                    format!("Server refused to {} community {}", block_text, community);
-                }
-            }
-            Err(e) => logger(format!("Got exception {}ing community {}: {}", block_text, community, e)),
-        }
+// LASIM is built without a console on Windows so the GUI does not open one, the command line borrows the
+// console of the shell it was started from so output is shown and confirmations can be answered.
+#[cfg(windows)]
+fn attach_parent_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
     }
-}
-
-async fn follow_communities(api: &lemmy::api::Api,
-    jwt_token: &str,
-    message_rate_limit: std::time::Duration,
-    mut logger: impl FnMut(String),
-    community_list: &Vec<String>,
-    follow: bool) {
-
-    let follow_text = if follow {
-        "follow"
-    } else {
-        "unfollow"
-    };
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
 
-    for community in community_list {
-        let community_details_result = api.fetch_community_by_name(jwt_token, community).await;
-        thread::sleep(message_rate_limit);
-
-        if community_details_result.is_err() {
-            logger(format!("Cannot find community {}, got exception {}",
-                           community,
-                           community_details_result.unwrap_err()));
-            continue;
-        }
-
-        let id = community_details_result.unwrap().community_view.community.id;
-        let follow_community_result = api.follow_community(jwt_token, id, follow).await;
-        thread::sleep(message_rate_limit);
-
-        match follow_community_result {
-            Ok(response) => {
-                if response.community_view.subscribed == lemmy_api_common::lemmy_db_schema::SubscribedType::NotSubscribed {
-                    format!("Server refused to {} community {}", follow_text, community);
-                }
-            }
-            Err(e) => logger(format!("Got exception {}ing community {}: {}", follow_text, community, e)),
-        }
+    // Fails when started without a console, e.g. with output redirected to a file, which needs no console anyway
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
     }
 }
 
-#[tokio::main]
-async fn process_upload(processing_instruction: ProcessingInstruction, mut logger: impl FnMut(String)) {
-    // Read original profile
-    let original_profile = match read_profile() {
-        Ok(profile) => profile,
-        Err(e) => {
-            logger(e);
-            return;
-        },
-    };
-
-    // Fetch data from UI
-    let mut instance = processing_instruction.instance.to_string();
-    let username = processing_instruction.username.to_string();
-    let password = processing_instruction.password.to_string();
-    let two_factor_token = match evaluate_two_factor_token(&processing_instruction.two_factor_token.to_string()) {
-        Ok(token) => token,
-        Err(e) => {
-            logger(format!("ERROR: Invalid 2FA Token - {}", e));
-            return;
-        },
-    };
-
-    if !instance.starts_with("http") {
-        instance.insert_str(0, "https://");
-    }
-    let instance_url_result = Url::parse(instance.as_str());
-    if instance_url_result.is_err() {
-        logger("ERROR: Invalid Instance URL".to_string());
-        return;
-    }
-    let instance_url = instance_url_result.unwrap();
-
-    let api = match lemmy::api::Api::new(instance_url).await {
-        Ok(api) => api,
-        Err(e) => {
-            logger(format!(
-                "ERROR: Invalid Instance URL (or instance is down) - {e}"
-            ));
-            return;
-        }
-    };
-
-    // Login
-    logger(format!("Logging in as {}", username));
-    let jwt_token_future = api.login(&username, &password, two_factor_token);
-    let jwt_token_result = block_on(jwt_token_future);
-    if jwt_token_result.is_err() {
-        logger(format!("ERROR: Failed Login - {}", jwt_token_result.unwrap_err()));
-        return;
-    }
-
-    let jwt_token = jwt_token_result.unwrap();
-    logger("Login Successful.".to_string());
-
-    // Fetch New Profile
-    let new_profile_future = api.fetch_profile_settings(&jwt_token);
-    let new_profile_result = block_on(new_profile_future);
-    if new_profile_result.is_err() {
-        logger(format!("ERROR: Failed to fetch Porfile - {}", new_profile_result.unwrap_err()));
-        return;
-    }
-    let new_profile_api = new_profile_result.unwrap();
-    logger("Existing Settings Downloaded. Calculating delta...".to_string());
-
-    // Convert
-    let new_profile = FromAPI::construct_profile(&new_profile_api);
-
-    // Calculating Differences
-    let global_settings = processing_instruction.global_settings;
-    let profile_changes = profile::calculate_changes(&original_profile, &new_profile);
-    let mut api_calls_needed = 0u32;
-    
-    if global_settings.upload_profile_settings {
-        logger("All profile settings from the original profile will be applied.".to_string());
-        api_calls_needed += 1;
-    }
-    
-    if global_settings.upload_user_blocks {
-        logger(format!("{} new users will be blocked", profile_changes.users_to_block.len()));
-        api_calls_needed += profile_changes.users_to_block.len() as u32 * 2;
-
-        if global_settings.sync_removals {
-            logger(format!("{} users will be unblocked", profile_changes.users_to_unblock.len()));
-            api_calls_needed += profile_changes.users_to_unblock.len() as u32 * 2;
-        }
-    }
-    
-    if global_settings.upload_community_blocks {
-        logger(format!("{} new communities will be blocked", profile_changes.communities_to_block.len()));
-        api_calls_needed += profile_changes.communities_to_block.len() as u32 * 2;
-
-        if global_settings.sync_removals {
-            logger(format!("{} communities will be unblocked", profile_changes.communities_to_unblock.len()));
-            api_calls_needed += profile_changes.communities_to_unblock.len() as u32 * 2;
-        }
-    }
-    
-    if global_settings.upload_community_subs {
-        logger(format!("{} new communities will be followed", profile_changes.communities_to_follow.len()));
-        api_calls_needed += profile_changes.communities_to_follow.len() as u32 * 2;
-
-        if global_settings.sync_removals {
-            logger(format!("{} communities will be unfollowed", profile_changes.communities_to_unfollow.len()));
-            api_calls_needed += profile_changes.communities_to_unfollow.len() as u32 * 2;
-        }
-    }
-
-    // Call API to actually apply changes to new account
-
-    // Account for Rate Limits - values get mapped as seen here: lemmy/src/api_routes_http.rs
-    let mut message_count_per_time_period = new_profile_api.site_view.local_site_rate_limit.message;
-    if message_count_per_time_period <= 0 {
-        message_count_per_time_period = 1;
-    }
-    let mut message_time_period_interval_sec = new_profile_api.site_view.local_site_rate_limit.message_per_second;
-    if message_time_period_interval_sec <= 0 {
-        message_time_period_interval_sec = 1;
-    }
-    let message_per_second = message_time_period_interval_sec as f64 / message_count_per_time_period as f64;
-    let message_rate_limit = std::time::Duration::from_millis((message_per_second * 1000.0).ceil() as u64);
-    let estimated_time_sec = (message_rate_limit.as_millis() as f64 * api_calls_needed as f64 / 1000.0) as u32;
-
-    if estimated_time_sec > 60 {
-        let minutes = estimated_time_sec / 60;
-        let remaining_seconds = estimated_time_sec % 60;
-        logger(format!("Estimated Upload Time: {}m {}s", minutes, remaining_seconds));
-    } else {
-        logger(format!("Estimated Upload Time: {}s", estimated_time_sec));
-    }
-    
-
-    // Block / Unblock Users
-    if global_settings.upload_user_blocks {
-        block_users(&api, &jwt_token, message_rate_limit, &mut logger, &profile_changes.users_to_block, true).await;
-        if global_settings.sync_removals {
-            block_users(&api, &jwt_token, message_rate_limit, &mut logger, &profile_changes.users_to_unblock, false).await;
-        }
-    }
-    
-    // Block Communities
-    if global_settings.upload_community_blocks {
-        block_communities(&api, &jwt_token, message_rate_limit, &mut logger, &profile_changes.communities_to_block, true).await;
-        if global_settings.sync_removals {
-            block_communities(&api, &jwt_token, message_rate_limit, &mut logger, &profile_changes.communities_to_unblock, false).await;
-        }
-    }
-    
-    // Follow Communities
-    if global_settings.upload_community_subs {
-        follow_communities(&api, &jwt_token, message_rate_limit, &mut logger, &profile_changes.communities_to_follow, true).await;
-        if global_settings.sync_removals {
-            follow_communities(&api, &jwt_token, message_rate_limit, &mut logger, &profile_changes.communities_to_unfollow, false).await;
-        }
-    }
-    
-    // Save profile settings
-    if global_settings.upload_profile_settings {
-        let save_settings_result = api.save_user_settings(&jwt_token, profile_changes.profile_settings).await;
-        if save_settings_result.is_err() {
-            logger(format!("Cannot save profile settings, got exception {}", save_settings_result.unwrap_err()));
-        }
-    }
-
-    logger("Finished!".to_string());
-}
+#[cfg(not(windows))]
+fn attach_parent_console() {}
 
 fn main() {
     // Setup some kind of logging for if we crash
@@ -521,58 +188,127 @@ fn main() {
         std::process::exit(1);
     }));
 
+    // Run headless when given any command line arguments
+    if std::env::args_os().len() > 1 {
+        attach_parent_console();
+        std::process::exit(cli::run());
+    }
+
     // Setup processing thread communication
-    let (instruct_tx, instruct_rx): (Sender<ProcessingInstruction>, Receiver<ProcessingInstruction>) = mpsc::channel();
+    let (instruct_tx, instruct_rx): (Sender<GuiInstruction>, Receiver<GuiInstruction>) = mpsc::channel();
     let instruct_tx_copy = instruct_tx.clone();
+    let (confirm_tx, confirm_rx): (Sender<bool>, Receiver<bool>) = mpsc::channel();
+    let confirm_tx_copy = confirm_tx.clone();
+    let download_cancellation = Cancellation::new();
+    let upload_cancellation = Cancellation::new();
+    let worker_download_cancellation = download_cancellation.clone();
+    let worker_upload_cancellation = upload_cancellation.clone();
+    let clicked_download_cancellation = download_cancellation.clone();
+    let clicked_upload_cancellation = upload_cancellation.clone();
+    let cancelled_download_cancellation = download_cancellation.clone();
+    let cancelled_upload_cancellation = upload_cancellation.clone();
 
     // Construct Slint App
     let app = App::new().unwrap();
     let app_weak: Weak<App> = app.as_weak();
     let app_control_page = app_weak.clone();
+    let app_confirm_page = app_weak.clone();
+    let app_cancel_page = app_weak.clone();
     let app_settings_page = app_weak.clone();
+    let app_merge_page = app_weak.clone();
     let app_apply_settings = app_weak.clone();
 
     // Main instruction processing thread
     let main_thread = thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
         loop {
-            let processing_instruction = instruct_rx.recv().unwrap();
-            
-            if processing_instruction.instruction_type == "Done" {
-                break;
-            } else if processing_instruction.instruction_type == "Download" {
-                // Closure madness: create a logger closure for updating the UI
-                let app_copy = app_weak.clone();
-                let logger = |text: String| {
-                    let app_internal_copy = app_copy.clone();
-                    slint::invoke_from_event_loop(move || {
-                        let original_text = app_internal_copy.unwrap().get_download_log_output();
-                        let new_text = format!("{}{}\n", original_text, text);
-                        app_internal_copy.unwrap().set_download_log_output(new_text.into())
-                    }).unwrap();
-                };
+            match instruct_rx.recv().unwrap() {
+                GuiInstruction::Done => break,
+                GuiInstruction::Download(processing_instruction) => {
+                    // Closure madness: create a logger closure for updating the UI
+                    let app_copy = app_weak.clone();
+                    let logger = |text: String| {
+                        let app_internal_copy = app_copy.clone();
+                        slint::invoke_from_event_loop(move || {
+                            let original_text = app_internal_copy.unwrap().get_download_log_output();
+                            let new_text = format!("{}{}\n", original_text, text);
+                            app_internal_copy.unwrap().set_download_log_output(new_text.into())
+                        }).ok(); // The window may already be closed
+                    };
+
+                    if let Err(e) = runtime.block_on(engine::process_download(processing_instruction, &logger, &worker_download_cancellation)) {
+                        logger(e);
+                    }
 
-                process_download(processing_instruction, logger);
-
-                slint::invoke_from_event_loop(move || {
-                    app_copy.unwrap().set_download_ui_enabled(true);
-                }).unwrap();
-            } else {
-                // Closure madness: same thing but for uploading
-                let app_copy = app_weak.clone();
-                let logger = |text: String| {
-                    let app_internal_copy = app_copy.clone();
                     slint::invoke_from_event_loop(move || {
-                        let original_text = app_internal_copy.unwrap().get_upload_log_output();
-                        let new_text = format!("{}{}\n", original_text, text);
-                        app_internal_copy.unwrap().set_upload_log_output(new_text.into())
-                    }).unwrap();
-                };
+                        app_copy.unwrap().set_download_ui_enabled(true);
+                        app_copy.unwrap().set_download_cancelling(false);
+                        apply_profile_names(app_copy);
+                    }).ok();
+                },
+                GuiInstruction::Compare(processing_instruction, compare_with) => {
+                    let app_copy = app_weak.clone();
+                    let logger = |text: String| {
+                        let app_internal_copy = app_copy.clone();
+                        slint::invoke_from_event_loop(move || {
+                            let original_text = app_internal_copy.unwrap().get_compare_log_output();
+                            let new_text = format!("{}{}\n", original_text, text);
+                            app_internal_copy.unwrap().set_compare_log_output(new_text.into())
+                        }).ok(); // The window may already be closed
+                    };
+
+                    let result = match compare_with {
+                        Some(compare_with) => engine::process_compare(&processing_instruction.profile_name, &compare_with, &logger),
+                        None => runtime.block_on(engine::process_compare_account(processing_instruction, &logger)),
+                    };
+                    if let Err(e) = result {
+                        logger(e);
+                    }
 
-                process_upload(processing_instruction, logger);
+                    slint::invoke_from_event_loop(move || {
+                        app_copy.unwrap().set_compare_ui_enabled(true);
+                    }).ok();
+                },
+                instruction @ (GuiInstruction::Upload(_) | GuiInstruction::Undo(_)) => {
+                    // Closure madness: same thing but for uploading, which undoing shares the page with
+                    let app_copy = app_weak.clone();
+                    let logger = |text: String| {
+                        let app_internal_copy = app_copy.clone();
+                        slint::invoke_from_event_loop(move || {
+                            let original_text = app_internal_copy.unwrap().get_upload_log_output();
+                            let new_text = format!("{}{}\n", original_text, text);
+                            app_internal_copy.unwrap().set_upload_log_output(new_text.into())
+                        }).ok(); // The window may already be closed
+                    };
+
+                    // Show the Apply/Cancel buttons and wait for one of them to be clicked
+                    let confirm = |_: &lasim::profile::ProfileChanges| {
+                        let app_internal_copy = app_copy.clone();
+                        slint::invoke_from_event_loop(move || {
+                            app_internal_copy.unwrap().set_upload_awaiting_confirmation(true);
+                        }).ok();
+
+                        return confirm_rx.recv().unwrap_or(false);
+                    };
+
+                    let result = match instruction {
+                        GuiInstruction::Upload(processing_instruction) => {
+                            runtime.block_on(engine::process_upload(processing_instruction, &logger, confirm, &worker_upload_cancellation))
+                        },
+                        GuiInstruction::Undo(processing_instruction) => {
+                            runtime.block_on(engine::process_undo(processing_instruction, &logger, confirm, &worker_upload_cancellation))
+                        },
+                        _ => unreachable!(),
+                    };
+                    if let Err(e) = result {
+                        logger(e);
+                    }
 
-                slint::invoke_from_event_loop(move || {
-                    app_copy.unwrap().set_upload_ui_enabled(true);
-                }).unwrap();
+                    slint::invoke_from_event_loop(move || {
+                        app_copy.unwrap().set_upload_ui_enabled(true);
+                        app_copy.unwrap().set_upload_cancelling(false);
+                    }).ok();
+                },
             }
         }
     });
@@ -580,90 +316,129 @@ fn main() {
     // Bind Control Page clicking action
     app.global::<ControlPageHandler>().on_clicked({
         move |window_type| {      
-            let global_settings = GlobalSettings { 
-                upload_profile_settings: app_control_page.unwrap().get_upload_profile_settings(),
-                upload_community_subs: app_control_page.unwrap().get_upload_community_subs(),
-                upload_community_blocks: app_control_page.unwrap().get_upload_community_blocks(),
-                upload_user_blocks: app_control_page.unwrap().get_upload_user_blocks(),
-                upload_user_saved_posts: app_control_page.unwrap().get_upload_user_saved_posts(),
-                sync_removals: app_control_page.unwrap().get_sync_removals(),
-                confirm_uploads: app_control_page.unwrap().get_confirm_uploads(),
-                write_api_profiles: app_control_page.unwrap().get_write_api_profiles(),
-            };
+            let global_settings = collect_global_settings(&app_control_page);
 
             if window_type == "Download" {
                 app_control_page.unwrap().set_download_log_output("".into());
                 app_control_page.unwrap().set_download_ui_enabled(false);
+                clicked_download_cancellation.reset();
 
                 let download_instruction = ProcessingInstruction {
-                    instruction_type: window_type,
-                    instance: app_control_page.unwrap().get_download_instance_url(),
-                    username: app_control_page.unwrap().get_download_username_input(),
-                    password: app_control_page.unwrap().get_download_password_input(),
-                    two_factor_token: app_control_page.unwrap().get_download_two_factor_input(),
+                    instance: app_control_page.unwrap().get_download_instance_url().to_string(),
+                    username: app_control_page.unwrap().get_download_username_input().to_string(),
+                    password: app_control_page.unwrap().get_download_password_input().to_string(),
+                    two_factor_token: app_control_page.unwrap().get_download_two_factor_input().to_string(),
                     global_settings: global_settings,
+                    profile_name: String::new(),
                 };
 
-                instruct_tx.send(download_instruction).unwrap();
+                instruct_tx.send(GuiInstruction::Download(download_instruction)).unwrap();
+            } else if window_type == "Compare" {
+                app_control_page.unwrap().set_compare_log_output("".into());
+                app_control_page.unwrap().set_compare_ui_enabled(false);
+
+                let compare_instruction = ProcessingInstruction {
+                    instance: app_control_page.unwrap().get_compare_instance_url().to_string(),
+                    username: app_control_page.unwrap().get_compare_username_input().to_string(),
+                    password: app_control_page.unwrap().get_compare_password_input().to_string(),
+                    two_factor_token: app_control_page.unwrap().get_compare_two_factor_input().to_string(),
+                    global_settings: global_settings,
+                    profile_name: app_control_page.unwrap().get_compare_selected_profile().to_string(),
+                };
+                let compare_with = app_control_page.unwrap().get_compare_selected_target().to_string();
+                let compare_with = match compare_with == COMPARE_WITH_ACCOUNT {
+                    true => None,
+                    false => Some(compare_with),
+                };
+
+                instruct_tx.send(GuiInstruction::Compare(compare_instruction, compare_with)).unwrap();
             } else {
                 app_control_page.unwrap().set_upload_log_output("".into());
                 app_control_page.unwrap().set_upload_ui_enabled(false);
+                clicked_upload_cancellation.reset();
 
                 let upload_instruction = ProcessingInstruction {
-                    instruction_type: window_type,
-                    instance: app_control_page.unwrap().get_upload_instance_url(),
-                    username: app_control_page.unwrap().get_upload_username_input(),
-                    password: app_control_page.unwrap().get_upload_password_input(),
-                    two_factor_token: app_control_page.unwrap().get_upload_two_factor_input(),
+                    instance: app_control_page.unwrap().get_upload_instance_url().to_string(),
+                    username: app_control_page.unwrap().get_upload_username_input().to_string(),
+                    password: app_control_page.unwrap().get_upload_password_input().to_string(),
+                    two_factor_token: app_control_page.unwrap().get_upload_two_factor_input().to_string(),
                     global_settings: global_settings,
+                    profile_name: app_control_page.unwrap().get_upload_selected_profile().to_string(),
                 };
 
-                instruct_tx.send(upload_instruction).unwrap();
+                if window_type == "Undo" {
+                    instruct_tx.send(GuiInstruction::Undo(upload_instruction)).unwrap();
+                } else {
+                    instruct_tx.send(GuiInstruction::Upload(upload_instruction)).unwrap();
+                }
+            }
+        }
+    });
+
+    // Bind Upload confirmation buttons
+    app.global::<ControlPageHandler>().on_confirmed({
+        move |_window_type, approved| {
+            app_confirm_page.unwrap().set_upload_awaiting_confirmation(false);
+            confirm_tx.send(approved).unwrap();
+        }
+    });
+
+    // Bind Cancel buttons shown while a download or upload is running
+    app.global::<ControlPageHandler>().on_cancelled({
+        move |window_type| {
+            if window_type == "Download" {
+                app_cancel_page.unwrap().set_download_cancelling(true);
+                cancelled_download_cancellation.cancel();
+            } else {
+                app_cancel_page.unwrap().set_upload_cancelling(true);
+                cancelled_upload_cancellation.cancel();
+            }
+        }
+    });
+
+    // Bind Merge button - merging only reads and writes stored profiles, so it runs right away
+    app.global::<MergePageHandler>().on_merged({
+        move || {
+            let profile_names: Vec<String> = app_merge_page.unwrap().get_merge_sources().iter()
+                .filter(|source| source.selected)
+                .map(|source| source.name.to_string())
+                .collect();
+            let settings_profile = app_merge_page.unwrap().get_merge_settings_profile().to_string();
+            let merged_profile_name = app_merge_page.unwrap().get_merge_profile_name().to_string();
+
+            let mut log_output = String::new();
+            let logger = |text: String| log_output.push_str(&format!("{}\n", text));
+            if let Err(e) = engine::process_merge(&profile_names, &settings_profile, &merged_profile_name, logger) {
+                log_output.push_str(&format!("{}\n", e));
             }
+
+            app_merge_page.unwrap().set_merge_log_output(log_output.into());
+            apply_profile_names(app_merge_page.clone());
         }
     });
 
     // Bind to toggline of settings
     app.global::<SettingsPageHandler>().on_toggled({
         move || {
-            let global_settings = GlobalSettings { 
-                upload_profile_settings: app_settings_page.unwrap().get_upload_profile_settings(),
-                upload_community_subs: app_settings_page.unwrap().get_upload_community_subs(),
-                upload_community_blocks: app_settings_page.unwrap().get_upload_community_blocks(),
-                upload_user_blocks: app_settings_page.unwrap().get_upload_user_blocks(),
-                upload_user_saved_posts: app_settings_page.unwrap().get_upload_user_saved_posts(),
-                sync_removals: app_settings_page.unwrap().get_sync_removals(),
-                confirm_uploads: app_settings_page.unwrap().get_confirm_uploads(),
-                write_api_profiles: app_settings_page.unwrap().get_write_api_profiles(),
-            };
+            let global_settings = collect_global_settings(&app_settings_page);
     
             write_global_settings(global_settings);
         }
     });
 
+    editor::bind_editor_page(&app);
+
     // Load Settings
-    apply_global_settings(app_apply_settings);
+    apply_global_settings(app_apply_settings.clone());
+    apply_profile_names(app_apply_settings);
 
     // Run GUI application
     app.run().unwrap();
 
-    // Cleanup
-    instruct_tx_copy.send(ProcessingInstruction {
-        instruction_type: "Done".into(),
-        instance: "".into(),
-        username: "".into(),
-        password: "".into(),
-        two_factor_token: "".into(),
-        global_settings: GlobalSettings { 
-            upload_profile_settings: false,
-            upload_community_subs: false,
-            upload_community_blocks: false,
-            upload_user_blocks: false,
-            upload_user_saved_posts: false,
-            sync_removals: false,
-            confirm_uploads: false,
-            write_api_profiles: false,
-        },
-    }).unwrap();
+    // Cleanup - stop any download or upload in progress, including one still waiting for confirmation
+    download_cancellation.cancel();
+    upload_cancellation.cancel();
+    confirm_tx_copy.send(false).unwrap();
+    instruct_tx_copy.send(GuiInstruction::Done).unwrap();
     main_thread.join().unwrap();
 }
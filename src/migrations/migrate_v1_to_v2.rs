@@ -1,6 +1,8 @@
-use crate::profile::ProfileConfiguration;
-use crate::profile::ProfileSettings;
-use std::path::Path;
+use crate::migrations::migrate_v2_to_v3::ProfileConfigurationV2;
+use crate::migrations::migrate_v2_to_v3::ProfileSettingsV2;
+use crate::migrations::profile_migrate::ProfileMigration;
+
+use serde_json::Value;
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ProfileSettingsV1 {
@@ -28,31 +30,32 @@ pub struct ProfileConfigurationV1 {
     pub profile_settings: ProfileSettingsV1,
 }
 
-const OLD_PROFILE_FILENAME: &str = "profile_v1.json";
+/// Converts profile_v1.json to profile_v2.json.
+pub struct MigrateV1ToV2;
 
-pub fn read_profile() -> Result<ProfileConfigurationV1, String> {
-    let path = Path::new(OLD_PROFILE_FILENAME);
-    let profile_json_result = std::fs::read_to_string(path);
-    let profile_json = match profile_json_result {
-        Ok(file) => file,
-        Err(_) => return Err(format!("ERROR: Failed to open {}", OLD_PROFILE_FILENAME)),
-    };
+impl ProfileMigration for MigrateV1ToV2 {
+    fn from_version(&self) -> u16 {
+        return 1;
+    }
 
-    let profile_local_result: Result<ProfileConfigurationV1, serde_json::Error> = serde_json::from_slice(profile_json.as_bytes());
-    let profile_local = match profile_local_result {
-        Ok(profile) => profile,
-        Err(e) => return Err(format!("ERROR: Failed to parse {} JSON - {}", OLD_PROFILE_FILENAME, e)),
-    };
+    fn migrate(&self, old_profile: Value) -> Result<Value, serde_json::Error> {
+        let old_profile: ProfileConfigurationV1 = serde_json::from_value(old_profile)?;
+        return serde_json::to_value(convert_profile(old_profile));
+    }
 
-    return Ok(profile_local);
+    fn downgrade(&self, new_profile: Value) -> Result<(Value, Vec<String>), serde_json::Error> {
+        let new_profile: ProfileConfigurationV2 = serde_json::from_value(new_profile)?;
+        let (old_profile, lost_fields) = revert_profile(new_profile);
+        return Ok((serde_json::to_value(old_profile)?, lost_fields));
+    }
 }
 
-pub fn convert_profile(old_profile: ProfileConfigurationV1) -> ProfileConfiguration {
-    let new_profile = ProfileConfiguration {
+pub fn convert_profile(old_profile: ProfileConfigurationV1) -> ProfileConfigurationV2 {
+    let new_profile = ProfileConfigurationV2 {
         blocked_users: old_profile.blocked_users,
         blocked_communities: old_profile.blocked_communities,
         followed_communities: old_profile.followed_communities,
-        profile_settings: ProfileSettings {
+        profile_settings: ProfileSettingsV2 {
             show_nsfw: old_profile.profile_settings.show_nsfw,
             show_scores: old_profile.profile_settings.show_scores,
             theme: old_profile.profile_settings.theme,
@@ -72,4 +75,36 @@ pub fn convert_profile(old_profile: ProfileConfigurationV1) -> ProfileConfigurat
     };
 
     return new_profile;
-}
\ No newline at end of file
+}
+
+/// Converts a profile back to version 1, listing the fields version 1 has no place for.
+pub fn revert_profile(new_profile: ProfileConfigurationV2) -> (ProfileConfigurationV1, Vec<String>) {
+    let mut lost_fields: Vec<String> = vec![];
+    if new_profile.profile_settings.infinite_scroll_enabled {
+        lost_fields.push("profile_settings.infinite_scroll_enabled (true)".to_string());
+    }
+
+    let old_profile = ProfileConfigurationV1 {
+        blocked_users: new_profile.blocked_users,
+        blocked_communities: new_profile.blocked_communities,
+        followed_communities: new_profile.followed_communities,
+        profile_settings: ProfileSettingsV1 {
+            show_nsfw: new_profile.profile_settings.show_nsfw,
+            show_scores: new_profile.profile_settings.show_scores,
+            theme: new_profile.profile_settings.theme,
+            default_sort_type: new_profile.profile_settings.default_sort_type,
+            default_listing_type: new_profile.profile_settings.default_listing_type,
+            interface_language: new_profile.profile_settings.interface_language,
+            show_avatars: new_profile.profile_settings.show_avatars,
+            send_notifications_to_email: new_profile.profile_settings.send_notifications_to_email,
+            bot_account: new_profile.profile_settings.bot_account,
+            show_bot_accounts: new_profile.profile_settings.show_bot_accounts,
+            show_read_posts: new_profile.profile_settings.show_read_posts,
+            show_new_post_notifs: new_profile.profile_settings.show_new_post_notifs,
+            discussion_languages: new_profile.profile_settings.discussion_languages,
+            open_links_in_new_tab: new_profile.profile_settings.open_links_in_new_tab,
+        },
+    };
+
+    return (old_profile, lost_fields);
+}
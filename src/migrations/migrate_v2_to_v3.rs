@@ -0,0 +1,153 @@
+use crate::lemmy::compat;
+use crate::profile::ProfileConfiguration;
+use crate::profile::ProfileSettings;
+use crate::migrations::profile_migrate::ProfileMigration;
+
+use serde_json::Value;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ProfileSettingsV2 {
+    pub show_nsfw: bool,
+    pub show_scores: bool,
+    pub theme: String,
+    pub default_sort_type: String,
+    pub default_listing_type: String,
+    pub interface_language: String,
+    pub show_avatars: bool,
+    pub send_notifications_to_email: bool,
+    pub bot_account: bool,
+    pub show_bot_accounts: bool,
+    pub show_read_posts: bool,
+    pub show_new_post_notifs: bool,
+    pub discussion_languages: Vec<i32>,
+    pub open_links_in_new_tab: bool,
+    pub infinite_scroll_enabled: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ProfileConfigurationV2 {
+    pub blocked_users: Vec<String>,
+    pub blocked_communities: Vec<String>,
+    pub followed_communities: Vec<String>,
+    pub profile_settings: ProfileSettingsV2,
+}
+
+/// Converts profile_v2.json to profile_v3.json.
+pub struct MigrateV2ToV3;
+
+impl ProfileMigration for MigrateV2ToV3 {
+    fn from_version(&self) -> u16 {
+        return 2;
+    }
+
+    fn migrate(&self, old_profile: Value) -> Result<Value, serde_json::Error> {
+        let old_profile: ProfileConfigurationV2 = serde_json::from_value(old_profile)?;
+        return serde_json::to_value(convert_profile(old_profile));
+    }
+
+    fn downgrade(&self, new_profile: Value) -> Result<(Value, Vec<String>), serde_json::Error> {
+        let new_profile: ProfileConfiguration = serde_json::from_value(new_profile)?;
+        let (old_profile, lost_fields) = revert_profile(new_profile);
+        return Ok((serde_json::to_value(old_profile)?, lost_fields));
+    }
+}
+
+pub fn convert_profile(old_profile: ProfileConfigurationV2) -> ProfileConfiguration {
+    let new_profile = ProfileConfiguration {
+        metadata: None,
+        blocked_users: old_profile.blocked_users,
+        blocked_communities: old_profile.blocked_communities,
+        blocked_instances: vec![],
+        followed_communities: old_profile.followed_communities,
+        profile_settings: ProfileSettings {
+            show_nsfw: old_profile.profile_settings.show_nsfw,
+            show_scores: old_profile.profile_settings.show_scores,
+            theme: old_profile.profile_settings.theme,
+            default_sort_type: old_profile.profile_settings.default_sort_type,
+            default_listing_type: old_profile.profile_settings.default_listing_type,
+            interface_language: old_profile.profile_settings.interface_language,
+            show_avatars: old_profile.profile_settings.show_avatars,
+            send_notifications_to_email: old_profile.profile_settings.send_notifications_to_email,
+            bot_account: old_profile.profile_settings.bot_account,
+            show_bot_accounts: old_profile.profile_settings.show_bot_accounts,
+            show_read_posts: old_profile.profile_settings.show_read_posts,
+            show_new_post_notifs: old_profile.profile_settings.show_new_post_notifs,
+            discussion_languages: old_profile.profile_settings.discussion_languages,
+            open_links_in_new_tab: old_profile.profile_settings.open_links_in_new_tab,
+            infinite_scroll_enabled: old_profile.profile_settings.infinite_scroll_enabled,
+            blur_nsfw: false,
+            auto_expand: false,
+            avatar: None,
+            banner: None,
+            display_name: None,
+            bio: None,
+            matrix_user_id: None,
+        },
+        saved_posts: vec![],
+    };
+
+    return new_profile;
+}
+
+/// Converts a profile back to version 2, listing the fields version 2 has no place for.
+pub fn revert_profile(new_profile: ProfileConfiguration) -> (ProfileConfigurationV2, Vec<String>) {
+    // Only fields holding something are listed, a field left at its default loses nothing
+    let mut lost_fields: Vec<String> = vec![];
+    if let Some(metadata) = &new_profile.metadata {
+        lost_fields.push(format!("metadata (downloaded from {} on {})", metadata.source_actor_id, metadata.downloaded_at));
+    }
+    if !new_profile.blocked_instances.is_empty() {
+        lost_fields.push(format!("blocked_instances ({} entries)", new_profile.blocked_instances.len()));
+    }
+    if !new_profile.saved_posts.is_empty() {
+        lost_fields.push(format!("saved_posts ({} entries)", new_profile.saved_posts.len()));
+    }
+    let settings = &new_profile.profile_settings;
+    for (name, value) in [("blur_nsfw", settings.blur_nsfw), ("auto_expand", settings.auto_expand)] {
+        if value {
+            lost_fields.push(format!("profile_settings.{} (true)", name));
+        }
+    }
+    // Older LASIM versions only know the 0.18 sort types and would replace a newer one with their own guess
+    let default_sort_type = compat::lemmy_018_sort_type(&settings.default_sort_type).to_string();
+    if default_sort_type != settings.default_sort_type {
+        lost_fields.push(format!("profile_settings.default_sort_type ({})", settings.default_sort_type));
+    }
+    let optional_settings = [
+        ("avatar", &settings.avatar),
+        ("banner", &settings.banner),
+        ("display_name", &settings.display_name),
+        ("bio", &settings.bio),
+        ("matrix_user_id", &settings.matrix_user_id),
+    ];
+    for (name, value) in optional_settings {
+        if let Some(value) = value {
+            lost_fields.push(format!("profile_settings.{} ({})", name, value));
+        }
+    }
+
+    let old_profile = ProfileConfigurationV2 {
+        blocked_users: new_profile.blocked_users,
+        blocked_communities: new_profile.blocked_communities,
+        followed_communities: new_profile.followed_communities,
+        profile_settings: ProfileSettingsV2 {
+            show_nsfw: new_profile.profile_settings.show_nsfw,
+            show_scores: new_profile.profile_settings.show_scores,
+            theme: new_profile.profile_settings.theme,
+            default_sort_type: default_sort_type,
+            default_listing_type: new_profile.profile_settings.default_listing_type,
+            interface_language: new_profile.profile_settings.interface_language,
+            show_avatars: new_profile.profile_settings.show_avatars,
+            send_notifications_to_email: new_profile.profile_settings.send_notifications_to_email,
+            bot_account: new_profile.profile_settings.bot_account,
+            show_bot_accounts: new_profile.profile_settings.show_bot_accounts,
+            show_read_posts: new_profile.profile_settings.show_read_posts,
+            show_new_post_notifs: new_profile.profile_settings.show_new_post_notifs,
+            discussion_languages: new_profile.profile_settings.discussion_languages,
+            open_links_in_new_tab: new_profile.profile_settings.open_links_in_new_tab,
+            infinite_scroll_enabled: new_profile.profile_settings.infinite_scroll_enabled,
+        },
+    };
+
+    return (old_profile, lost_fields);
+}
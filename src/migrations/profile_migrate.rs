@@ -1,69 +1,191 @@
 use crate::profile;
-use crate::migrations;
+use crate::migrations::migrate_v1_to_v2::MigrateV1ToV2;
+use crate::migrations::migrate_v2_to_v3::MigrateV2ToV3;
+
+use serde_json::Value;
+
 use std::path::Path;
+use std::path::PathBuf;
 
 const PROFILE_FILENAME_START: &str = "profile_v";
 const PROFILE_FILENAME_END: &str = ".json";
-const PROFILE_CURRENT_VERSION: u16 = 2;
+const PROFILE_CURRENT_VERSION: u16 = 3;
+
+/// A single step that converts a profile from one version to the next.
+///
+/// Profiles are passed between steps as JSON, so steps can be chained without knowing each other's types.
+pub trait ProfileMigration {
+    /// The profile version this step reads, it writes the version after it.
+    fn from_version(&self) -> u16;
+
+    /// Converts a profile of `from_version` to `from_version + 1`.
+    fn migrate(&self, old_profile: Value) -> Result<Value, serde_json::Error>;
+
+    /// Converts a profile of `from_version + 1` back to `from_version`, listing the fields the older version cannot hold.
+    fn downgrade(&self, new_profile: Value) -> Result<(Value, Vec<String>), serde_json::Error>;
+}
+
+/// A profile converted to an older version, for older LASIM versions to read.
+#[derive(Debug, Clone)]
+pub struct ProfileDowngrade {
+    pub profile_json: Value,
+    /// Every field left out along the way, with the value that was lost
+    pub lost_fields: Vec<String>,
+}
+
+// Every migration step, one for each profile version before the current one
+const MIGRATIONS: [&dyn ProfileMigration; 2] = [&MigrateV1ToV2, &MigrateV2ToV3];
+
+/// The newest profile file in a directory, and the files named like profiles that cannot be read as one.
+#[derive(Debug, Default, Clone)]
+pub struct ProfileFiles {
+    pub latest: Option<(u16, PathBuf)>,
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Reads the version from a profile filename, e.g. 2 for profile_v2.json.
+///
+/// Returns None for any other file, including stray ones like profile_vbackup.json or profile_v02.json.
+pub fn parse_profile_version(filename: &str) -> Option<u16> {
+    let profile_version_string = filename.strip_prefix(PROFILE_FILENAME_START)?.strip_suffix(PROFILE_FILENAME_END)?;
+    match profile_version_string.parse::<u16>() {
+        // Only the exact name LASIM writes, so each version has a single file
+        Ok(profile_version) if profile_version > 0 && profile_version.to_string() == profile_version_string => return Some(profile_version),
+        _ => return None,
+    }
+}
+
+pub fn find_profile_files(directory: &Path) -> ProfileFiles {
+    let mut profile_files = ProfileFiles::default();
+    let directory_items = match std::fs::read_dir(directory) {
+        Ok(directory_items) => directory_items,
+        Err(_) => return profile_files,
+    };
 
-pub fn read_latest_profile() -> Result<profile::ProfileConfiguration, String> {
-    // Identify latest profile version
-    let mut latest_profile_version: u16 = 0;
-    let directory_items = std::fs::read_dir("./").unwrap();
     for dir_entry in directory_items.flatten() {
-        let metadata = std::fs::metadata(dir_entry.path());
-        if metadata.is_ok_and(|m| m.is_file()) {
-            let filename = String::from(dir_entry.file_name().to_str().unwrap());
-            if filename.starts_with(PROFILE_FILENAME_START) && filename.ends_with(PROFILE_FILENAME_END) {
-                let end_index = filename.find(PROFILE_FILENAME_END).unwrap();
-                let profile_version_string = filename.get(PROFILE_FILENAME_START.char_indices().count()..end_index);
-                let profile_version = profile_version_string.unwrap().parse::<u16>().unwrap();
-
-                if profile_version > latest_profile_version {
-                    latest_profile_version = profile_version;
+        let filename = dir_entry.file_name().to_string_lossy().to_string();
+        if !filename.starts_with(PROFILE_FILENAME_START) {
+            continue;
+        }
+
+        let path = dir_entry.path();
+        match parse_profile_version(&filename) {
+            Some(profile_version) if path.is_file() => {
+                if profile_files.latest.as_ref().map_or(true, |(latest_version, _)| profile_version > *latest_version) {
+                    profile_files.latest = Some((profile_version, path));
                 }
-            }
+            },
+            _ => profile_files.skipped.push(path),
         }
     }
+    profile_files.skipped.sort();
+
+    return profile_files;
+}
+
+pub fn find_latest_profile_version(directory: &Path) -> Option<u16> {
+    return find_profile_files(directory).latest.map(|(profile_version, _)| profile_version);
+}
+
+/// Converts a profile of the given version to the current version, one migration step at a time.
+pub fn migrate_profile(mut profile_json: Value, profile_version: u16) -> Result<profile::ProfileConfiguration, String> {
+    if profile_version > PROFILE_CURRENT_VERSION {
+        return Err(format!("ERROR: Profile version {} was written by a newer version of LASIM, which is needed to read it", profile_version));
+    }
 
-    // Convert as necessary
-    let mut profile_v1: Option<migrations::migrate_v1_to_v2::ProfileConfigurationV1> = None;
-    let mut profile_v2: Option<profile::ProfileConfiguration> = None;
+    for version in profile_version..PROFILE_CURRENT_VERSION {
+        let migration = match MIGRATIONS.iter().find(|migration| migration.from_version() == version) {
+            Some(migration) => migration,
+            None => return Err(format!("ERROR: Cannot convert profile version {}", version)),
+        };
 
-    if latest_profile_version == 1 {
-        let read_result = migrations::migrate_v1_to_v2::read_profile();
-        profile_v1 = match read_result {
-            Ok(profile) => Some(profile),
-            Err(e) => return Err(e),
+        profile_json = match migration.migrate(profile_json) {
+            Ok(new_profile) => new_profile,
+            Err(e) => return Err(format!("ERROR: Failed to parse {} JSON - {}", get_profile_name(version), e)),
         };
-        latest_profile_version = 2;
     }
 
-    if latest_profile_version == PROFILE_CURRENT_VERSION {
-        if let Some(profile_v1) = profile_v1 {
-            profile_v2 = Some(migrations::migrate_v1_to_v2::convert_profile(profile_v1));
-        } else {
-            let filename = format!("profile_v{}.json", PROFILE_CURRENT_VERSION);
-            let path = Path::new(filename.as_str());
-            let profile_json_result = std::fs::read_to_string(path);
-            let profile_json = match profile_json_result {
-                Ok(file) => file,
-                Err(_) => return Err(format!("ERROR: Failed to open {}", filename)),
-            };
-
-            let profile_local_result: Result<profile::ProfileConfiguration, serde_json::Error> = serde_json::from_slice(profile_json.as_bytes());
-            let profile_local = match profile_local_result {
-                Ok(profile) => profile,
-                Err(e) => return Err(format!("ERROR: Failed to parse {} JSON - {}", filename, e)),
-            };
-
-            profile_v2 = Some(profile_local);
+    match serde_json::from_value(profile_json) {
+        Ok(profile) => return Ok(profile),
+        Err(e) => return Err(format!("ERROR: Failed to parse {} JSON - {}", get_profile_name(profile_version), e)),
+    }
+}
+
+/// Converts a current profile to an older profile version, one migration step at a time.
+pub fn downgrade_profile(profile: &profile::ProfileConfiguration, profile_version: u16) -> Result<ProfileDowngrade, String> {
+    if profile_version == 0 || profile_version > PROFILE_CURRENT_VERSION {
+        return Err(format!("ERROR: Cannot convert to profile version {}, only versions 1 to {} exist", profile_version, PROFILE_CURRENT_VERSION));
+    }
+
+    let mut profile_json = match serde_json::to_value(profile) {
+        Ok(profile_json) => profile_json,
+        Err(e) => return Err(format!("ERROR: Failed to convert profile to JSON - {}", e)),
+    };
+    let mut lost_fields: Vec<String> = vec![];
+    for version in (profile_version..PROFILE_CURRENT_VERSION).rev() {
+        let migration = match MIGRATIONS.iter().find(|migration| migration.from_version() == version) {
+            Some(migration) => migration,
+            None => return Err(format!("ERROR: Cannot convert to profile version {}", version)),
+        };
+
+        match migration.downgrade(profile_json) {
+            Ok((old_profile, step_lost_fields)) => {
+                profile_json = old_profile;
+                lost_fields.extend(step_lost_fields);
+            },
+            Err(e) => return Err(format!("ERROR: Failed to convert profile to version {} - {}", version, e)),
         }
     }
 
-    return profile_v2.ok_or_else(|| "ERROR: No saved profiles found. Use download option first!".to_string());
+    return Ok(ProfileDowngrade {
+        profile_json: profile_json,
+        lost_fields: lost_fields,
+    });
+}
+
+/// Reads the newest profile file in the directory, logging the files named like profiles that are skipped.
+pub fn read_latest_profile(directory: &Path, mut logger: impl FnMut(String)) -> Result<profile::ProfileConfiguration, String> {
+    let profile_files = find_profile_files(directory);
+    for path in &profile_files.skipped {
+        logger(format!("Skipping {}, it is not a profile file like {}", path.display(), get_latest_profile_name()));
+    }
+
+    let (latest_profile_version, path) = match profile_files.latest {
+        Some(latest) => latest,
+        None => return Err("ERROR: No saved profiles found. Use download option first!".to_string()),
+    };
+
+    let profile_json = read_profile_json(&path)?;
+    return migrate_profile(profile_json, latest_profile_version);
 }
 
 pub fn get_latest_profile_name() -> String {
-    return format!("{}{}{}", PROFILE_FILENAME_START, PROFILE_CURRENT_VERSION, PROFILE_FILENAME_END);
-}
\ No newline at end of file
+    return get_profile_name(PROFILE_CURRENT_VERSION);
+}
+
+pub fn get_profile_name(profile_version: u16) -> String {
+    return format!("{}{}{}", PROFILE_FILENAME_START, profile_version, PROFILE_FILENAME_END);
+}
+
+/// Reads a single profile file of any version, converting it to the latest version.
+///
+/// Files not named like a profile version are read as the current version.
+pub fn read_profile_file(path: &Path) -> Result<profile::ProfileConfiguration, String> {
+    let filename = path.file_name().map(|filename| filename.to_string_lossy().to_string()).unwrap_or_default();
+    let profile_version = parse_profile_version(&filename).unwrap_or(PROFILE_CURRENT_VERSION);
+
+    let profile_json = read_profile_json(path)?;
+    return migrate_profile(profile_json, profile_version);
+}
+
+fn read_profile_json(path: &Path) -> Result<Value, String> {
+    let profile_json = match std::fs::read_to_string(path) {
+        Ok(file) => file,
+        Err(_) => return Err(format!("ERROR: Failed to open {}", path.display())),
+    };
+
+    match serde_json::from_slice(profile_json.as_bytes()) {
+        Ok(profile_json) => return Ok(profile_json),
+        Err(e) => return Err(format!("ERROR: Failed to parse {} JSON - {}", path.display(), e)),
+    }
+}
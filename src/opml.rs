@@ -0,0 +1,155 @@
+use crate::profile;
+
+use url::Url;
+
+// Lemmy serves each community's posts as RSS at https://host/feeds/c/name.xml
+const COMMUNITY_FEED_PATH: &str = "/feeds/c/";
+const FEED_EXTENSION: &str = ".xml";
+
+/// Communities found in an OPML feed list, and the feeds that are not Lemmy community feeds.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OpmlImport {
+    pub communities: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+/// An OPML feed list written from a profile's communities, and the entries that are not communities.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OpmlExport {
+    pub opml: String,
+    pub exported: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+/// The RSS feed of a community written as name@host, e.g. https://lemmy.ml/feeds/c/rust.xml
+pub fn community_feed_url(community: &str) -> Result<String, String> {
+    profile::validate_name_at_host(community)?;
+    let (name, host) = community.split_once('@').unwrap();
+    return Ok(format!("https://{}{}{}{}", host, COMMUNITY_FEED_PATH, name, FEED_EXTENSION));
+}
+
+/// The community a Lemmy community feed belongs to, as name@host, or None for any other feed.
+pub fn community_from_feed_url(feed_url: &str) -> Option<String> {
+    let url = Url::parse(feed_url).ok()?;
+    let name = url.path().strip_prefix(COMMUNITY_FEED_PATH)?.strip_suffix(FEED_EXTENSION)?;
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str()?, port),
+        None => url.host_str()?.to_string(),
+    };
+
+    let community = format!("{}@{}", name, host);
+    return profile::validate_name_at_host(&community).ok().map(|_| community);
+}
+
+fn escape_xml(text: &str) -> String {
+    return text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;");
+}
+
+fn unescape_xml(text: &str) -> String {
+    return text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+}
+
+/// Writes the communities as an OPML 2.0 feed list, one RSS outline per community, skipping malformed entries.
+pub fn export_opml(title: &str, communities: &[String]) -> OpmlExport {
+    let mut opml_export = OpmlExport::default();
+    let mut opml = String::new();
+    opml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    opml.push_str("<opml version=\"2.0\">\n");
+    opml.push_str(&format!("  <head>\n    <title>{}</title>\n  </head>\n", escape_xml(title)));
+    opml.push_str("  <body>\n");
+
+    for community in communities {
+        let feed_url = match community_feed_url(community) {
+            Ok(feed_url) => feed_url,
+            Err(_) => {
+                opml_export.skipped.push(community.clone());
+                continue;
+            },
+        };
+        let (name, host) = community.split_once('@').unwrap();
+        let html_url = format!("https://{}/c/{}", host, name);
+        opml.push_str(&format!("    <outline type=\"rss\" text=\"{0}\" title=\"{0}\" xmlUrl=\"{1}\" htmlUrl=\"{2}\"/>\n",
+                               escape_xml(community),
+                               escape_xml(&feed_url),
+                               escape_xml(&html_url)));
+        opml_export.exported.push(community.clone());
+    }
+
+    opml.push_str("  </body>\n");
+    opml.push_str("</opml>\n");
+    opml_export.opml = opml;
+    return opml_export;
+}
+
+fn attribute_value(tag: &str, attribute: &str) -> Option<String> {
+    // Attributes are matched by name regardless of case, quoted with either kind of quote
+    let lowercase_tag = tag.to_ascii_lowercase();
+    let mut search_start = 0;
+
+    while let Some(found) = lowercase_tag[search_start..].find(&format!("{}=", attribute.to_ascii_lowercase())) {
+        let name_start = search_start + found;
+        let value_start = name_start + attribute.len() + 1;
+        search_start = value_start;
+
+        // Skip matches inside a longer attribute name, e.g. htmlUrl when looking for url
+        let preceding = tag[..name_start].chars().last();
+        if preceding.is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+
+        let quote = tag[value_start..].chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let value_end = tag[value_start + 1..].find(quote)?;
+        return Some(unescape_xml(&tag[value_start + 1..value_start + 1 + value_end]));
+    }
+
+    return None;
+}
+
+/// Reads the feed URL of every outline in an OPML document, including nested ones.
+pub fn read_feed_urls(opml: &str) -> Vec<String> {
+    let mut feed_urls: Vec<String> = vec![];
+    let mut remaining = opml;
+
+    while let Some(tag_start) = remaining.find("<outline") {
+        let tag_end = match remaining[tag_start..].find('>') {
+            Some(tag_end) => tag_start + tag_end,
+            None => break,
+        };
+
+        if let Some(feed_url) = attribute_value(&remaining[tag_start..tag_end], "xmlUrl") {
+            feed_urls.push(feed_url);
+        }
+        remaining = &remaining[tag_end..];
+    }
+
+    return feed_urls;
+}
+
+/// Finds the Lemmy communities in an OPML feed list, skipping any other feeds.
+pub fn import_opml(opml: &str) -> OpmlImport {
+    let mut opml_import = OpmlImport::default();
+
+    for feed_url in read_feed_urls(opml) {
+        match community_from_feed_url(&feed_url) {
+            Some(community) => {
+                if !opml_import.communities.contains(&community) {
+                    opml_import.communities.push(community);
+                }
+            },
+            None => opml_import.skipped.push(feed_url),
+        }
+    }
+
+    return opml_import;
+}
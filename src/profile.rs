@@ -1,3 +1,6 @@
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ProfileSettings {
     pub show_nsfw: bool,
@@ -15,25 +18,118 @@ pub struct ProfileSettings {
     pub discussion_languages: Vec<i32>,
     pub open_links_in_new_tab: bool,
     pub infinite_scroll_enabled: bool,
+    /// Only applied by Lemmy 0.19 and later
+    #[serde(default)]
+    pub blur_nsfw: bool,
+    /// Only applied by Lemmy 0.19 and later
+    #[serde(default)]
+    pub auto_expand: bool,
+    #[serde(default)]
+    pub avatar: Option<String>,
+    #[serde(default)]
+    pub banner: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub bio: Option<String>,
+    #[serde(default)]
+    pub matrix_user_id: Option<String>,
+}
+
+/// Where, when and by what a profile was downloaded.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct ProfileMetadata {
+    /// The account the profile came from, e.g. https://lemmy.ml/u/alice
+    pub source_actor_id: String,
+    pub instance_url: String,
+    /// The Lemmy version the instance reported, e.g. 0.19.3
+    pub backend_version: String,
+    pub lasim_version: String,
+    /// When the profile was downloaded, in UTC, e.g. 2023-07-01T12:30:00Z
+    pub downloaded_at: String,
+}
+
+impl ProfileMetadata {
+    /// Describes a profile downloaded just now by this version of LASIM.
+    pub fn new(source_actor_id: &str, instance_url: &str, backend_version: &str) -> ProfileMetadata {
+        return ProfileMetadata {
+            source_actor_id: source_actor_id.to_string(),
+            instance_url: instance_url.to_string(),
+            backend_version: backend_version.to_string(),
+            lasim_version: env!("CARGO_PKG_VERSION").to_string(),
+            downloaded_at: format_utc_timestamp(SystemTime::now()),
+        };
+    }
+}
+
+/// Formats a time as UTC in RFC 3339, e.g. 2023-07-01T12:30:00Z
+pub fn format_utc_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+    let seconds_of_day = seconds % 86400;
+
+    // Days since 1970-01-01 to a calendar date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let shifted_days = seconds / 86400 + 719468;
+    let era = shifted_days / 146097;
+    let day_of_era = shifted_days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    return format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                   year,
+                   month,
+                   day,
+                   seconds_of_day / 3600,
+                   seconds_of_day % 3600 / 60,
+                   seconds_of_day % 60);
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ProfileConfiguration {
+    /// Kept at the top of the profile file; profiles downloaded before it existed, or merged, have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ProfileMetadata>,
     pub blocked_users: Vec<String>,
     pub blocked_communities: Vec<String>,
+    /// Instances blocked as a whole, e.g. lemmy.ml - only Lemmy 0.19 and later can block instances
+    #[serde(default)]
+    pub blocked_instances: Vec<String>,
     pub followed_communities: Vec<String>,
     pub profile_settings: ProfileSettings,
+    #[serde(default)]
+    pub saved_posts: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct ProfileSettingChange {
+    pub name: String,
+    pub current_value: String,
+    pub new_value: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ProfileChanges {
     pub users_to_block: Vec<String>,
     pub users_to_unblock: Vec<String>,
     pub communities_to_block: Vec<String>,
     pub communities_to_unblock: Vec<String>,
+    #[serde(default)]
+    pub instances_to_block: Vec<String>,
+    #[serde(default)]
+    pub instances_to_unblock: Vec<String>,
     pub communities_to_follow: Vec<String>,
     pub communities_to_unfollow: Vec<String>,
+    pub posts_to_save: Vec<String>,
+    pub posts_to_unsave: Vec<String>,
     pub profile_settings: ProfileSettings,
+    pub profile_settings_changes: Vec<ProfileSettingChange>,
 }
 
 fn calculate_users_to_block(original_profile: &ProfileConfiguration, new_profile: &ProfileConfiguration) -> Vec<String> {
@@ -80,6 +176,20 @@ fn calculate_communities_to_block(original_profile: &ProfileConfiguration, new_p
     return new_block_requests;
 }
 
+fn calculate_instances_to_block(original_profile: &ProfileConfiguration, new_profile: &ProfileConfiguration) -> Vec<String> {
+    let original_blocks = &(original_profile.blocked_instances);
+    let new_blocks = &(new_profile.blocked_instances);
+    let mut new_block_requests: Vec<String> = vec![];
+
+    for orig_block_instance in original_blocks {
+        if !new_blocks.contains(orig_block_instance) {
+            new_block_requests.push(orig_block_instance.clone());
+        }
+    }
+
+    return new_block_requests;
+}
+
 fn calculate_communities_to_follow(original_profile: &ProfileConfiguration, new_profile: &ProfileConfiguration) -> Vec<String> {
     let original_follows= &(original_profile.followed_communities);
     let new_follows = &(new_profile.followed_communities);
@@ -102,14 +212,134 @@ fn calculate_communities_to_follow(original_profile: &ProfileConfiguration, new_
     return new_follow_requests;
 }
 
+fn calculate_posts_to_save(original_profile: &ProfileConfiguration, new_profile: &ProfileConfiguration) -> Vec<String> {
+    let original_saves = &(original_profile.saved_posts);
+    let new_saves = &(new_profile.saved_posts);
+    let mut new_save_requests: Vec<String> = vec![];
+
+    for orig_saved_post in original_saves {
+        let mut already_saved = false;
+        for new_saved_post in new_saves {
+            if orig_saved_post == new_saved_post {
+                already_saved = true;
+                break;
+            }
+        }
+
+        if !already_saved {
+            new_save_requests.push(orig_saved_post.clone());
+        }
+    }
+
+    return new_save_requests;
+}
+
+fn calculate_profile_settings_changes(original_settings: &ProfileSettings, new_settings: &ProfileSettings) -> Vec<ProfileSettingChange> {
+    // Compare the serialized forms so every setting is covered without listing each field
+    let original_values = serde_json::to_value(original_settings).unwrap();
+    let new_values = serde_json::to_value(new_settings).unwrap();
+    let mut setting_changes: Vec<ProfileSettingChange> = vec![];
+
+    if let (Some(original_map), Some(new_map)) = (original_values.as_object(), new_values.as_object()) {
+        for (name, original_value) in original_map {
+            let current_value = new_map.get(name).unwrap_or(&serde_json::Value::Null);
+            if original_value != current_value {
+                setting_changes.push(ProfileSettingChange {
+                    name: name.clone(),
+                    current_value: current_value.to_string(),
+                    new_value: original_value.to_string(),
+                });
+            }
+        }
+    }
+
+    return setting_changes;
+}
+
 pub fn calculate_changes(original_profile: &ProfileConfiguration, new_profile: &ProfileConfiguration) -> ProfileChanges {
     return ProfileChanges {
         users_to_block: calculate_users_to_block(original_profile, new_profile),
         users_to_unblock: calculate_users_to_block(new_profile, original_profile),
         communities_to_block: calculate_communities_to_block(original_profile, new_profile),
         communities_to_unblock: calculate_communities_to_block(new_profile, original_profile),
+        instances_to_block: calculate_instances_to_block(original_profile, new_profile),
+        instances_to_unblock: calculate_instances_to_block(new_profile, original_profile),
         communities_to_follow: calculate_communities_to_follow(original_profile, new_profile),
         communities_to_unfollow: calculate_communities_to_follow(new_profile, original_profile),
+        posts_to_save: calculate_posts_to_save(original_profile, new_profile),
+        posts_to_unsave: calculate_posts_to_save(new_profile, original_profile),
         profile_settings: original_profile.profile_settings.clone(),
+        profile_settings_changes: calculate_profile_settings_changes(&original_profile.profile_settings, &new_profile.profile_settings),
+    };
+}
+
+/// Checks that a user or community is written as name@host, e.g. rust@lemmy.ml
+pub fn validate_name_at_host(entry: &str) -> Result<(), String> {
+    let (name, host) = match entry.split_once('@') {
+        Some((name, host)) => (name, host),
+        None => return Err(format!("{} is not written as name@host", entry)),
+    };
+
+    // Lemmy names are letters, numbers and _, users federated from other software may also use - and .
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+        return Err(format!("{} has an invalid name - only letters, numbers, _, - and . are allowed", entry));
+    }
+
+    if !is_valid_host(host) {
+        return Err(format!("{} has an invalid instance", entry));
+    }
+
+    return Ok(());
+}
+
+/// Checks that a blocked instance is written as its host, e.g. lemmy.ml
+pub fn validate_instance(entry: &str) -> Result<(), String> {
+    if !is_valid_host(entry) {
+        return Err(format!("{} is not an instance - write just the host, e.g. lemmy.ml", entry));
+    }
+
+    return Ok(());
+}
+
+fn is_valid_host(host: &str) -> bool {
+    let hostname = host.split_once(':').map(|(hostname, _)| hostname).unwrap_or(host);
+    let valid_hostname = !hostname.is_empty()
+        && hostname.split('.').all(|label| {
+            !label.is_empty() && !label.starts_with('-') && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    let valid_port = match host.split_once(':') {
+        Some((_, port)) => port.parse::<u16>().is_ok(),
+        None => true,
+    };
+
+    return valid_hostname && valid_port;
+}
+
+fn merge_lists<'a>(lists: impl Iterator<Item = &'a Vec<String>>) -> Vec<String> {
+    let mut merged_list: Vec<String> = vec![];
+    for list in lists {
+        for entry in list {
+            if !merged_list.contains(entry) {
+                merged_list.push(entry.clone());
+            }
+        }
+    }
+
+    return merged_list;
+}
+
+/// Combines several profiles into one that follows, blocks and saves everything any of them did.
+///
+/// Entries keep the order they first appear in, and the profile settings are taken as given.
+pub fn merge_profiles(profiles: &[ProfileConfiguration], profile_settings: &ProfileSettings) -> ProfileConfiguration {
+    return ProfileConfiguration {
+        metadata: None,
+        blocked_users: merge_lists(profiles.iter().map(|profile| &profile.blocked_users)),
+        blocked_communities: merge_lists(profiles.iter().map(|profile| &profile.blocked_communities)),
+        blocked_instances: merge_lists(profiles.iter().map(|profile| &profile.blocked_instances)),
+        followed_communities: merge_lists(profiles.iter().map(|profile| &profile.followed_communities)),
+        profile_settings: profile_settings.clone(),
+        saved_posts: merge_lists(profiles.iter().map(|profile| &profile.saved_posts)),
     };
 }
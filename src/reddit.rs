@@ -0,0 +1,111 @@
+use crate::profile;
+
+use std::path::Path;
+
+/// Name of the file listing subscriptions in a Reddit data export.
+pub const SUBSCRIBED_SUBREDDITS_FILENAME: &str = "subscribed_subreddits.csv";
+const SUBREDDIT_HEADER: &str = "subreddit";
+
+/// Which Lemmy community each subreddit was matched to, and the subreddits that had no match.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RedditImport {
+    /// Pairs of subreddit and community, e.g. ("rust", "rust@lemmy.ml")
+    pub matched: Vec<(String, String)>,
+    pub unmatched: Vec<String>,
+}
+
+impl RedditImport {
+    /// The matched communities, without duplicates, in the order of the subreddits.
+    pub fn communities(&self) -> Vec<String> {
+        let mut communities: Vec<String> = vec![];
+        for (_, community) in &self.matched {
+            if !communities.contains(community) {
+                communities.push(community.clone());
+            }
+        }
+
+        return communities;
+    }
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => return Ok(contents.lines().map(|line| line.trim().to_string()).collect()),
+        Err(e) => return Err(format!("ERROR: Cannot read file - {}: {}", path.display(), e)),
+    }
+}
+
+fn parse_field(field: &str) -> String {
+    return field.trim().trim_matches('"').trim().to_string();
+}
+
+/// Reddit names are case insensitive and often written as r/name.
+fn normalize_subreddit(subreddit: &str) -> String {
+    let subreddit = subreddit.trim_start_matches('/');
+    let subreddit = subreddit.strip_prefix("r/").unwrap_or(subreddit);
+    return subreddit.to_lowercase();
+}
+
+/// Reads the subreddits from a Reddit export's `subscribed_subreddits.csv`.
+pub fn read_subscribed_subreddits(path: &Path) -> Result<Vec<String>, String> {
+    let mut subreddits: Vec<String> = vec![];
+
+    for line in read_lines(path)? {
+        // Only the first column is the subreddit name
+        let subreddit = parse_field(line.split(',').next().unwrap_or(""));
+        if subreddit.is_empty() || subreddit.eq_ignore_ascii_case(SUBREDDIT_HEADER) {
+            continue;
+        }
+        subreddits.push(subreddit);
+    }
+
+    return Ok(subreddits);
+}
+
+/// Reads a mapping file with one `subreddit,community` pair per line, e.g. `rust,rust@lemmy.ml`.
+///
+/// Blank lines, lines starting with # and a `subreddit,community` header are skipped.
+pub fn read_community_mapping(path: &Path) -> Result<Vec<(String, String)>, String> {
+    let mut mapping: Vec<(String, String)> = vec![];
+
+    for (line_number, line) in read_lines(path)?.iter().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (subreddit, community) = match line.split_once(',') {
+            Some((subreddit, community)) => (parse_field(subreddit), parse_field(community)),
+            None => return Err(format!("ERROR: {} line {} should be subreddit,community", path.display(), line_number + 1)),
+        };
+        if subreddit.eq_ignore_ascii_case(SUBREDDIT_HEADER) {
+            continue;
+        }
+
+        // Lemmy links communities as !name@host
+        let community = community.trim_start_matches('!').to_string();
+        if let Err(e) = profile::validate_name_at_host(&community) {
+            return Err(format!("ERROR: {} line {} - {}", path.display(), line_number + 1, e));
+        }
+        mapping.push((subreddit, community));
+    }
+
+    return Ok(mapping);
+}
+
+/// Matches each subreddit against the mapping, ignoring case and any r/ prefix.
+pub fn match_subreddits(subreddits: &[String], mapping: &[(String, String)]) -> RedditImport {
+    let mut reddit_import = RedditImport::default();
+
+    for subreddit in subreddits {
+        let mapped_community = mapping.iter()
+            .find(|(mapped_subreddit, _)| normalize_subreddit(mapped_subreddit) == normalize_subreddit(subreddit))
+            .map(|(_, community)| community.clone());
+
+        match mapped_community {
+            Some(community) => reddit_import.matched.push((subreddit.clone(), community)),
+            None => reddit_import.unmatched.push(subreddit.clone()),
+        }
+    }
+
+    return reddit_import;
+}
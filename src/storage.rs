@@ -0,0 +1,105 @@
+use crate::migrations::profile_migrate;
+
+use url::Url;
+
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+const DATA_DIRECTORY: &str = ".lasim";
+const DATA_DIRECTORY_VARIABLE: &str = "LASIM_DATA_DIR";
+const PROFILES_DIRECTORY: &str = "profiles";
+
+/// Name of the profile kept in the working directory by LASIM versions before per-account storage.
+pub const WORKING_DIRECTORY_PROFILE: &str = "Working Directory";
+
+/// Names the stored profile of an account, e.g. alice@lemmy.world
+///
+/// A port is kept after a _, e.g. alice@127.0.0.1_8536, as profile names cannot hold a colon.
+pub fn profile_name(username: &str, instance: &Url) -> String {
+    let host = instance.host_str().unwrap_or("unknown");
+    match instance.port() {
+        Some(port) => return format!("{}@{}_{}", username, host, port),
+        None => return format!("{}@{}", username, host),
+    }
+}
+
+/// Profiles are kept in ~/.lasim/profiles, unless LASIM_DATA_DIR points somewhere else.
+pub fn get_profiles_directory() -> Result<PathBuf, String> {
+    if let Some(data_directory) = std::env::var_os(DATA_DIRECTORY_VARIABLE) {
+        return Ok(PathBuf::from(data_directory).join(PROFILES_DIRECTORY));
+    }
+
+    match home::home_dir() {
+        Some(home_dir) => return Ok(home_dir.join(DATA_DIRECTORY).join(PROFILES_DIRECTORY)),
+        None => return Err("ERROR: Cannot identify home directory.".to_string()),
+    }
+}
+
+/// Checks a profile name is a single folder name, so the profile cannot be read or written outside the profiles directory.
+pub fn validate_profile_name(profile_name: &str) -> Result<(), String> {
+    let mut components = Path::new(profile_name).components();
+    let single_folder = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    if !single_folder || profile_name.contains(['/', '\\', ':']) || profile_name.chars().any(char::is_control) {
+        return Err(format!("ERROR: {} is not a valid profile name, use a name like alice@lemmy.world", profile_name));
+    }
+
+    return Ok(());
+}
+
+pub fn get_profile_directory(profile_name: &str) -> Result<PathBuf, String> {
+    if profile_name == WORKING_DIRECTORY_PROFILE {
+        return Ok(PathBuf::from("./"));
+    }
+
+    validate_profile_name(profile_name)?;
+    return Ok(get_profiles_directory()?.join(profile_name));
+}
+
+pub fn create_profile_directory(profile_name: &str) -> Result<PathBuf, String> {
+    let directory = get_profile_directory(profile_name)?;
+    match std::fs::create_dir_all(&directory) {
+        Ok(_) => return Ok(directory),
+        Err(e) => return Err(format!("ERROR: Cannot create directory - {}: {}", directory.display(), e)),
+    }
+}
+
+fn contains_profile(directory: &Path) -> bool {
+    return profile_migrate::find_latest_profile_version(directory).is_some();
+}
+
+/// Lists the names of all stored profiles, sorted by name.
+pub fn list_profiles() -> Vec<String> {
+    let mut profile_names: Vec<String> = vec![];
+
+    if let Ok(profiles_directory) = get_profiles_directory() {
+        if let Ok(directory_items) = std::fs::read_dir(profiles_directory) {
+            for dir_entry in directory_items.flatten() {
+                if dir_entry.path().is_dir() && contains_profile(&dir_entry.path()) {
+                    profile_names.push(dir_entry.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+    profile_names.sort();
+
+    if contains_profile(Path::new("./")) {
+        profile_names.push(WORKING_DIRECTORY_PROFILE.to_string());
+    }
+
+    return profile_names;
+}
+
+/// Picks the stored profile to use, falling back to the only stored profile when none was chosen.
+pub fn resolve_profile_name(profile_name: &str) -> Result<String, String> {
+    if !profile_name.is_empty() {
+        return Ok(profile_name.to_string());
+    }
+
+    let profile_names = list_profiles();
+    match profile_names.len() {
+        0 => return Err("ERROR: No saved profiles found. Use download option first!".to_string()),
+        1 => return Ok(profile_names[0].clone()),
+        _ => return Err(format!("ERROR: Choose which saved profile to use: {}", profile_names.join(", "))),
+    }
+}
@@ -0,0 +1,136 @@
+use crate::lemmy::compat;
+use crate::profile;
+
+use std::collections::HashSet;
+
+/// How serious a problem with a profile is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The profile cannot be uploaded as it is
+    Error,
+    /// The profile can be uploaded, but probably not as intended
+    Warning,
+}
+
+/// A single problem found in a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// The profile field the problem is in, e.g. followed_communities
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.severity {
+            Severity::Error => return write!(f, "ERROR: {} - {}", self.field, self.message),
+            Severity::Warning => return write!(f, "WARNING: {} - {}", self.field, self.message),
+        }
+    }
+}
+
+/// Everything found wrong with a profile, in the order the fields appear in the profile.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    pub fn error_count(&self) -> usize {
+        return self.findings.iter().filter(|finding| finding.severity == Severity::Error).count();
+    }
+
+    pub fn warning_count(&self) -> usize {
+        return self.findings.iter().filter(|finding| finding.severity == Severity::Warning).count();
+    }
+
+    pub fn has_errors(&self) -> bool {
+        return self.error_count() > 0;
+    }
+
+    fn add(&mut self, severity: Severity, field: &str, message: String) {
+        self.findings.push(Finding {
+            severity: severity,
+            field: field.to_string(),
+            message: message,
+        });
+    }
+}
+
+/// Checks a profile for malformed entries, duplicates, contradictions and settings no Lemmy version knows.
+///
+/// Malformed entries are only warnings, as they are skipped when uploading, see remove_malformed_entries.
+pub fn validate_profile(profile_local: &profile::ProfileConfiguration) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let name_at_host_lists = [
+        ("blocked_users", &profile_local.blocked_users),
+        ("blocked_communities", &profile_local.blocked_communities),
+        ("followed_communities", &profile_local.followed_communities),
+    ];
+    for (field, entries) in name_at_host_lists {
+        check_entries(&mut report, field, entries, profile::validate_name_at_host);
+    }
+    check_entries(&mut report, "blocked_instances", &profile_local.blocked_instances, profile::validate_instance);
+
+    let mut saved_posts: HashSet<&String> = HashSet::new();
+    for post in &profile_local.saved_posts {
+        if !saved_posts.insert(post) {
+            report.add(Severity::Warning, "saved_posts", format!("{} is listed more than once", post));
+        }
+    }
+
+    // Lemmy names are case insensitive, so entries differing only in case are the same community
+    let blocked_communities: HashSet<String> = profile_local.blocked_communities.iter().map(|entry| entry.to_lowercase()).collect();
+    for community in &profile_local.followed_communities {
+        if blocked_communities.contains(&community.to_lowercase()) {
+            report.add(Severity::Warning, "followed_communities", format!("{} is both followed and blocked", community));
+        }
+    }
+
+    let profile_settings = &profile_local.profile_settings;
+    if !compat::is_known_sort_type(&profile_settings.default_sort_type) {
+        report.add(Severity::Error, "profile_settings.default_sort_type", format!("{} is not a sort type Lemmy knows", profile_settings.default_sort_type));
+    }
+    if !compat::is_known_listing_type(&profile_settings.default_listing_type) {
+        report.add(Severity::Error, "profile_settings.default_listing_type", format!("{} is not a listing type Lemmy knows", profile_settings.default_listing_type));
+    }
+
+    return report;
+}
+
+fn check_entries(report: &mut ValidationReport, field: &str, entries: &[String], validate: impl Fn(&str) -> Result<(), String>) {
+    let mut seen: HashSet<String> = HashSet::new();
+    for entry in entries {
+        if let Err(e) = validate(entry) {
+            report.add(Severity::Warning, field, format!("{}, it is skipped when uploading", e));
+        }
+        if !seen.insert(entry.to_lowercase()) {
+            report.add(Severity::Warning, field, format!("{} is listed more than once", entry));
+        }
+    }
+}
+
+/// Removes every entry the upload has to skip because it is malformed, returning them as field: entry.
+pub fn remove_malformed_entries(profile_local: &mut profile::ProfileConfiguration) -> Vec<String> {
+    let mut removed_entries: Vec<String> = vec![];
+    let entry_lists: [(&str, &mut Vec<String>, fn(&str) -> Result<(), String>); 4] = [
+        ("blocked_users", &mut profile_local.blocked_users, profile::validate_name_at_host),
+        ("blocked_communities", &mut profile_local.blocked_communities, profile::validate_name_at_host),
+        ("followed_communities", &mut profile_local.followed_communities, profile::validate_name_at_host),
+        ("blocked_instances", &mut profile_local.blocked_instances, profile::validate_instance),
+    ];
+
+    for (field, entries, validate) in entry_lists {
+        entries.retain(|entry| {
+            if validate(entry).is_ok() {
+                return true;
+            }
+            removed_entries.push(format!("{}: {}", field, entry));
+            return false;
+        });
+    }
+
+    return removed_entries;
+}
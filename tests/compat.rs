@@ -0,0 +1,43 @@
+use lasim::lemmy::compat;
+use lasim::lemmy::compat::Backend;
+
+use serde_json::json;
+
+#[test]
+fn backend_follows_reported_version() {
+    assert_eq!(Backend::from_version("0.18.3"), Backend::Lemmy018);
+    assert_eq!(Backend::from_version("0.19.0-rc.1"), Backend::Lemmy019);
+    assert_eq!(Backend::from_version("v0.19.3"), Backend::Lemmy019);
+    assert_eq!(Backend::from_version("1.0.0"), Backend::Lemmy019);
+    assert_eq!(Backend::from_version("unknown"), Backend::Lemmy018);
+}
+
+#[test]
+fn lemmy_019_response_reads_as_018() {
+    let mut response = json!({
+        "local_user_view": {
+            "local_user": { "admin": true, "default_sort_type": "Scaled", "validator_time": "2023-07-01T00:00:00.123456Z" },
+            "person": { "actor_id": "https://lemmy.ml/u/me", "bot_account": false, "published": "2023-07-01T00:00:00+00:00" },
+            "counts": { "person_id": 1, "post_count": 0, "comment_count": 0 },
+        },
+        "post": { "name": "2023-07-01T00:00:00Z was a good day" },
+        "counts": { "hot_rank": 1.7 },
+    });
+    compat::adapt_response(&mut response);
+
+    let local_user_view = &response["local_user_view"];
+    assert_eq!(local_user_view["person"]["admin"], true);
+    assert_eq!(local_user_view["person"]["published"], "2023-07-01T00:00:00");
+    assert_eq!(local_user_view["local_user"]["validator_time"], "2023-07-01T00:00:00.123456");
+    assert_eq!(local_user_view["local_user"]["default_sort_type"], "Active");
+    assert_eq!(local_user_view["counts"]["id"], 0);
+    assert_eq!(local_user_view["counts"]["post_score"], 0);
+    assert_eq!(response["post"]["name"], "2023-07-01T00:00:00Z was a good day");
+    assert_eq!(response["counts"]["hot_rank"], 2);
+}
+
+#[test]
+fn lemmy_019_requests_leave_out_auth() {
+    let params = compat::request_params(json!({ "auth": "jwt", "community_id": 1, "follow": true }));
+    assert_eq!(params, json!({ "community_id": 1, "follow": true }));
+}
@@ -0,0 +1,188 @@
+use lasim::migrations::migrate_v1_to_v2::MigrateV1ToV2;
+use lasim::migrations::migrate_v2_to_v3::MigrateV2ToV3;
+use lasim::migrations::profile_migrate;
+use lasim::migrations::profile_migrate::ProfileMigration;
+
+use serde_json::json;
+
+use std::path::PathBuf;
+
+fn profile_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("lasim-migrations-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&directory).unwrap();
+    return directory;
+}
+
+fn profile_v2() -> serde_json::Value {
+    return json!({
+        "blocked_users": ["spammer@lemmy.world"],
+        "blocked_communities": ["memes@lemmy.world"],
+        "followed_communities": ["rust@lemmy.ml"],
+        "profile_settings": {
+            "show_nsfw": true,
+            "show_scores": true,
+            "theme": "browser",
+            "default_sort_type": "New",
+            "default_listing_type": "Local",
+            "interface_language": "browser",
+            "show_avatars": true,
+            "send_notifications_to_email": false,
+            "bot_account": false,
+            "show_bot_accounts": true,
+            "show_read_posts": true,
+            "show_new_post_notifs": false,
+            "discussion_languages": [37],
+            "open_links_in_new_tab": false,
+            "infinite_scroll_enabled": true,
+        },
+    });
+}
+
+#[test]
+fn profile_v2_migrates_to_v3() {
+    let directory = profile_directory("v2");
+    std::fs::write(directory.join("profile_v2.json"), profile_v2().to_string()).unwrap();
+
+    let profile = profile_migrate::read_latest_profile(&directory, |_| {}).unwrap();
+    assert_eq!(profile.followed_communities, vec!["rust@lemmy.ml"]);
+    assert!(profile.saved_posts.is_empty());
+    assert!(profile.blocked_instances.is_empty());
+    assert!(profile.profile_settings.infinite_scroll_enabled);
+    assert!(!profile.profile_settings.blur_nsfw);
+    assert_eq!(profile.profile_settings.bio, None);
+}
+
+#[test]
+fn profile_v1_migrates_through_v2() {
+    let directory = profile_directory("v1");
+    let mut profile_v1 = profile_v2();
+    profile_v1["profile_settings"].as_object_mut().unwrap().remove("infinite_scroll_enabled");
+    std::fs::write(directory.join("profile_v1.json"), profile_v1.to_string()).unwrap();
+
+    let profile = profile_migrate::read_latest_profile(&directory, |_| {}).unwrap();
+    assert_eq!(profile.blocked_users, vec!["spammer@lemmy.world"]);
+    assert!(profile.saved_posts.is_empty());
+    assert!(!profile.profile_settings.infinite_scroll_enabled);
+    assert!(profile.blocked_instances.is_empty());
+}
+
+#[test]
+fn v1_to_v2_step_adds_infinite_scroll() {
+    let mut profile_v1 = profile_v2();
+    profile_v1["profile_settings"].as_object_mut().unwrap().remove("infinite_scroll_enabled");
+
+    assert_eq!(MigrateV1ToV2.from_version(), 1);
+    let migrated = MigrateV1ToV2.migrate(profile_v1).unwrap();
+    let mut expected = profile_v2();
+    expected["profile_settings"]["infinite_scroll_enabled"] = json!(false);
+    assert_eq!(migrated, expected);
+}
+
+#[test]
+fn v2_to_v3_step_adds_newer_fields() {
+    assert_eq!(MigrateV2ToV3.from_version(), 2);
+    let migrated = MigrateV2ToV3.migrate(profile_v2()).unwrap();
+    assert_eq!(migrated["blocked_instances"], json!([]));
+    assert_eq!(migrated["saved_posts"], json!([]));
+    assert_eq!(migrated["profile_settings"]["blur_nsfw"], false);
+    assert_eq!(migrated["profile_settings"]["bio"], json!(null));
+
+    let mut malformed = profile_v2();
+    malformed.as_object_mut().unwrap().remove("followed_communities");
+    assert!(MigrateV2ToV3.migrate(malformed).is_err());
+}
+
+#[test]
+fn profile_version_is_read_from_filename() {
+    assert_eq!(profile_migrate::parse_profile_version("profile_v2.json"), Some(2));
+    assert_eq!(profile_migrate::parse_profile_version("profile_v12.json"), Some(12));
+    for filename in ["profile_vbackup.json", "profile_v.json", "profile_v0.json", "profile_v02.json", "profile_v+2.json", "profile_v2.json.bak", "api_profile_download.json"] {
+        assert_eq!(profile_migrate::parse_profile_version(filename), None, "{} is not a profile", filename);
+    }
+}
+
+#[test]
+fn stray_files_are_skipped() {
+    let directory = profile_directory("stray");
+    std::fs::write(directory.join("profile_v2.json"), profile_v2().to_string()).unwrap();
+    std::fs::write(directory.join("profile_vbackup.json"), "{}").unwrap();
+    std::fs::create_dir_all(directory.join("profile_v9.json")).unwrap();
+    std::fs::write(directory.join("profile_v09.json"), "{}").unwrap();
+
+    assert_eq!(profile_migrate::find_latest_profile_version(&directory), Some(2));
+    let mut log: Vec<String> = vec![];
+    let profile = profile_migrate::read_latest_profile(&directory, |text| log.push(text)).unwrap();
+    assert_eq!(profile.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(log.len(), 3);
+    assert!(log.iter().any(|line| line.starts_with("Skipping") && line.contains("profile_v09.json")));
+}
+
+#[test]
+fn newer_profile_versions_are_reported() {
+    let directory = profile_directory("newer");
+    std::fs::write(directory.join("profile_v2.json"), profile_v2().to_string()).unwrap();
+    std::fs::write(directory.join("profile_v99.json"), "{}").unwrap();
+
+    let error = profile_migrate::read_latest_profile(&directory, |_| {}).unwrap_err();
+    assert!(error.contains("newer version of LASIM"), "{}", error);
+}
+
+#[test]
+fn profile_downgrades_to_every_older_version() {
+    let directory = profile_directory("downgrade");
+    std::fs::write(directory.join("profile_v2.json"), profile_v2().to_string()).unwrap();
+    let mut profile = profile_migrate::read_latest_profile(&directory, |_| {}).unwrap();
+    profile.blocked_instances = vec!["lemmy.ml".to_string()];
+    profile.saved_posts = vec!["https://lemmy.ml/post/1".to_string()];
+    profile.profile_settings.bio = Some("Hello".to_string());
+
+    let profile_downgrade = profile_migrate::downgrade_profile(&profile, 3).unwrap();
+    assert!(profile_downgrade.lost_fields.is_empty());
+
+    let profile_downgrade = profile_migrate::downgrade_profile(&profile, 2).unwrap();
+    assert_eq!(profile_downgrade.profile_json, profile_v2());
+    assert!(profile_downgrade.lost_fields.contains(&"blocked_instances (1 entries)".to_string()));
+    assert!(profile_downgrade.lost_fields.contains(&"saved_posts (1 entries)".to_string()));
+    assert!(profile_downgrade.lost_fields.contains(&"profile_settings.bio (Hello)".to_string()));
+    // Fields holding nothing are not listed
+    assert_eq!(profile_downgrade.lost_fields.len(), 3);
+
+    let profile_downgrade = profile_migrate::downgrade_profile(&profile, 1).unwrap();
+    assert!(profile_downgrade.profile_json["profile_settings"].get("infinite_scroll_enabled").is_none());
+    assert_eq!(profile_downgrade.profile_json["followed_communities"], json!(["rust@lemmy.ml"]));
+    assert!(profile_downgrade.lost_fields.contains(&"profile_settings.infinite_scroll_enabled (true)".to_string()));
+
+    assert!(profile_migrate::downgrade_profile(&profile, 0).is_err());
+    assert!(profile_migrate::downgrade_profile(&profile, 4).is_err());
+}
+
+#[test]
+fn downgrade_replaces_sort_types_older_versions_do_not_know() {
+    let mut profile = profile_migrate::migrate_profile(profile_v2(), 2).unwrap();
+    profile.profile_settings.default_sort_type = "Scaled".to_string();
+
+    let profile_downgrade = profile_migrate::downgrade_profile(&profile, 2).unwrap();
+    assert_eq!(profile_downgrade.profile_json["profile_settings"]["default_sort_type"], json!("Active"));
+    assert_eq!(profile_downgrade.lost_fields, vec!["profile_settings.default_sort_type (Scaled)"]);
+
+    let profile_downgrade = profile_migrate::downgrade_profile(&profile, 1).unwrap();
+    assert_eq!(profile_downgrade.profile_json["profile_settings"]["default_sort_type"], json!("Active"));
+}
+
+#[test]
+fn downgrade_lists_nothing_for_unset_fields() {
+    let mut profile = profile_migrate::migrate_profile(profile_v2(), 2).unwrap();
+    profile.profile_settings.infinite_scroll_enabled = false;
+
+    let profile_downgrade = profile_migrate::downgrade_profile(&profile, 1).unwrap();
+    assert!(profile_downgrade.lost_fields.is_empty());
+}
+
+#[test]
+fn downgraded_profile_migrates_back() {
+    let profile_downgrade = profile_migrate::downgrade_profile(&profile_migrate::migrate_profile(profile_v2(), 2).unwrap(), 1).unwrap();
+
+    let profile = profile_migrate::migrate_profile(profile_downgrade.profile_json, 1).unwrap();
+    assert_eq!(profile.blocked_communities, vec!["memes@lemmy.world"]);
+    assert_eq!(profile.profile_settings.discussion_languages, vec![37]);
+}
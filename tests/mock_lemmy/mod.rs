@@ -0,0 +1,718 @@
+// A stand-in Lemmy 0.18 server covering the API calls LASIM makes, which can also act as Lemmy 0.19.
+//
+// Responses are built as JSON rather than lemmy_api_common structs; any field LASIM does not read is
+// filled with a harmless placeholder so the real response types deserialize.
+
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::header;
+use hyper::Body;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::Server;
+use hyper::StatusCode;
+use serde_json::json;
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const TIMESTAMP: &str = "2023-07-01T00:00:00";
+const USER_ID: i32 = 1;
+
+pub struct MockFailure {
+    pub status: u16,
+    pub remaining: u32,
+}
+
+/// Everything the mock instance knows about, shared with the test so it can be inspected afterwards.
+pub struct MockState {
+    pub host: String,
+    /// The version reported by /api/v3/site, anything starting with 0.19 switches to the 0.19 API
+    pub version: String,
+    pub username: String,
+    pub password: String,
+    pub known_users: Vec<String>,
+    pub known_communities: Vec<String>,
+    pub known_posts: Vec<String>,
+    /// Instances that can be blocked, which is only possible on 0.19
+    pub known_instances: Vec<String>,
+    pub blocked_users: Vec<String>,
+    pub blocked_communities: Vec<String>,
+    pub blocked_instances: Vec<String>,
+    pub followed_communities: Vec<String>,
+    pub saved_posts: Vec<String>,
+    /// Images pict-rs serves from /pictrs/image/<file>, by file
+    pub images: HashMap<String, Vec<u8>>,
+    pub settings: serde_json::Map<String, Value>,
+    pub rate_limit: (i32, i32),
+    pub failures: HashMap<String, MockFailure>,
+    pub requests: Vec<String>,
+}
+
+impl MockState {
+    pub fn new(host: &str, username: &str, password: &str) -> MockState {
+        let settings = json!({
+            "show_nsfw": false,
+            "show_scores": true,
+            "theme": "browser",
+            "default_sort_type": "Active",
+            "default_listing_type": "Local",
+            "interface_language": "browser",
+            "show_avatars": true,
+            "send_notifications_to_email": false,
+            "bot_account": false,
+            "show_bot_accounts": true,
+            "show_read_posts": true,
+            "show_new_post_notifs": false,
+            "discussion_languages": [],
+            "open_links_in_new_tab": false,
+            "infinite_scroll_enabled": false,
+        });
+
+        return MockState {
+            host: host.to_string(),
+            version: "0.18.3".to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            known_users: vec![],
+            known_communities: vec![],
+            known_posts: vec![],
+            known_instances: vec![],
+            blocked_users: vec![],
+            blocked_communities: vec![],
+            blocked_instances: vec![],
+            followed_communities: vec![],
+            saved_posts: vec![],
+            images: HashMap::new(),
+            settings: settings.as_object().unwrap().clone(),
+            rate_limit: (999, 1),
+            failures: HashMap::new(),
+            requests: vec![],
+        };
+    }
+
+    /// Makes the next `times` requests to `path` fail with `status`.
+    pub fn fail(&mut self, path: &str, status: u16, times: u32) {
+        self.failures.insert(path.to_string(), MockFailure { status: status, remaining: times });
+    }
+
+    pub fn count_requests(&self, request: &str) -> usize {
+        return self.requests.iter().filter(|r| r.as_str() == request).count();
+    }
+
+    fn jwt(&self) -> String {
+        return format!("mock-jwt-{}", self.username);
+    }
+
+    fn is_lemmy_019(&self) -> bool {
+        return self.version.starts_with("0.19");
+    }
+
+    fn is_authorized(&self, query: &HashMap<String, String>, body: &Value, bearer: Option<&str>) -> bool {
+        let jwt = self.jwt();
+        if self.is_lemmy_019() {
+            // 0.19 only reads the JWT from the Authorization header
+            return bearer == Some(jwt.as_str());
+        }
+
+        let query_auth = query.get("auth").map(|auth| auth == &jwt).unwrap_or(false);
+        let body_auth = body.get("auth").and_then(|auth| auth.as_str()).map(|auth| auth == jwt).unwrap_or(false);
+        return query_auth || body_auth;
+    }
+
+    fn site_response(&self, logged_in: bool) -> Value {
+        let my_user = if logged_in {
+            let follows: Vec<Value> = self.followed_communities.iter()
+                .map(|name| json!({ "community": self.community(name), "follower": self.me() }))
+                .collect();
+            let community_blocks: Vec<Value> = self.blocked_communities.iter()
+                .map(|name| json!({ "person": self.me(), "community": self.community(name) }))
+                .collect();
+            let person_blocks: Vec<Value> = self.blocked_users.iter()
+                .map(|name| json!({ "person": self.me(), "target": self.person(name) }))
+                .collect();
+
+            let mut my_user = json!({
+                "local_user_view": {
+                    "local_user": self.local_user(),
+                    "person": self.me(),
+                    "counts": person_counts(USER_ID),
+                },
+                "follows": follows,
+                "moderates": [],
+                "community_blocks": community_blocks,
+                "person_blocks": person_blocks,
+                "discussion_languages": self.settings.get("discussion_languages").cloned().unwrap_or(json!([])),
+            });
+            if self.is_lemmy_019() {
+                let instance_blocks: Vec<Value> = self.blocked_instances.iter()
+                    .map(|domain| json!({ "person": self.me(), "instance": self.instance(domain), "site": null }))
+                    .collect();
+                my_user["instance_blocks"] = json!(instance_blocks);
+            }
+            my_user
+        } else {
+            Value::Null
+        };
+
+        return json!({
+            "site_view": {
+                "site": {
+                    "id": 1,
+                    "name": "Mock Lemmy",
+                    "published": TIMESTAMP,
+                    "actor_id": format!("https://{}/", self.host),
+                    "last_refreshed_at": TIMESTAMP,
+                    "inbox_url": format!("https://{}/site_inbox", self.host),
+                    "public_key": "",
+                    "instance_id": 1,
+                },
+                "local_site": {
+                    "id": 1,
+                    "site_id": 1,
+                    "site_setup": true,
+                    "enable_downvotes": true,
+                    "enable_nsfw": true,
+                    "community_creation_admin_only": false,
+                    "require_email_verification": false,
+                    "private_instance": false,
+                    "default_theme": "browser",
+                    "default_post_listing_type": "Local",
+                    "hide_modlog_mod_names": true,
+                    "application_email_admins": false,
+                    "actor_name_max_length": 20,
+                    "federation_enabled": true,
+                    "captcha_enabled": false,
+                    "captcha_difficulty": "medium",
+                    "published": TIMESTAMP,
+                    "registration_mode": "Open",
+                    "reports_email_admins": false,
+                },
+                "local_site_rate_limit": {
+                    "id": 1,
+                    "local_site_id": 1,
+                    "message": self.rate_limit.0,
+                    "message_per_second": self.rate_limit.1,
+                    "post": 999,
+                    "post_per_second": 1,
+                    "register": 999,
+                    "register_per_second": 1,
+                    "image": 999,
+                    "image_per_second": 1,
+                    "comment": 999,
+                    "comment_per_second": 1,
+                    "search": 999,
+                    "search_per_second": 1,
+                    "published": TIMESTAMP,
+                },
+                "counts": {
+                    "id": 1,
+                    "site_id": 1,
+                    "users": 1,
+                    "posts": 0,
+                    "comments": 0,
+                    "communities": 0,
+                    "users_active_day": 0,
+                    "users_active_week": 0,
+                    "users_active_month": 0,
+                    "users_active_half_year": 0,
+                },
+            },
+            "admins": [],
+            "version": self.version,
+            "my_user": my_user,
+            "all_languages": [],
+            "discussion_languages": [],
+            "taglines": [],
+            "custom_emojis": [],
+        });
+    }
+
+    fn local_user(&self) -> Value {
+        let mut local_user = json!({
+            "id": USER_ID,
+            "person_id": USER_ID,
+            "email": format!("{}@mail.example", self.username),
+            "email_verified": true,
+            "accepted_application": true,
+            "validator_time": TIMESTAMP,
+        });
+        if self.is_lemmy_019() {
+            local_user["admin"] = json!(false);
+            local_user["blur_nsfw"] = json!(false);
+            local_user["auto_expand"] = json!(false);
+        }
+        overlay(&mut local_user, &self.settings);
+        return local_user;
+    }
+
+    fn me(&self) -> Value {
+        let mut person = person_json(USER_ID, &format!("https://{}/u/{}", self.host, self.username), &self.username);
+        overlay(&mut person, &self.settings);
+        return person;
+    }
+
+    fn person(&self, name: &str) -> Value {
+        let id = self.known_users.iter().position(|user| user == name).map(|index| index as i32 + 2).unwrap_or(0);
+        let (username, host) = split_name(name);
+        return person_json(id, &format!("https://{}/u/{}", host, username), username);
+    }
+
+    fn instance(&self, domain: &str) -> Value {
+        let id = self.known_instances.iter().position(|instance| instance == domain).map(|index| index as i32 + 1).unwrap_or(0);
+        return json!({ "id": id, "domain": domain, "published": TIMESTAMP });
+    }
+
+    fn community(&self, name: &str) -> Value {
+        let id = self.known_communities.iter().position(|community| community == name).map(|index| index as i32 + 1).unwrap_or(0);
+        let (community_name, host) = split_name(name);
+        return json!({
+            "id": id,
+            "name": community_name,
+            "title": community_name,
+            "removed": false,
+            "published": TIMESTAMP,
+            "deleted": false,
+            "nsfw": false,
+            "actor_id": format!("https://{}/c/{}", host, community_name),
+            "local": false,
+            "public_key": "",
+            "last_refreshed_at": TIMESTAMP,
+            "followers_url": format!("https://{}/c/{}/followers", host, community_name),
+            "inbox_url": format!("https://{}/c/{}/inbox", host, community_name),
+            "hidden": false,
+            "posting_restricted_to_mods": false,
+            "instance_id": 1,
+        });
+    }
+
+    fn community_view(&self, name: &str) -> Value {
+        let id = self.known_communities.iter().position(|community| community == name).map(|index| index as i32 + 1).unwrap_or(0);
+        let subscribed = if self.followed_communities.iter().any(|community| community == name) {
+            "Subscribed"
+        } else {
+            "NotSubscribed"
+        };
+
+        return json!({
+            "community": self.community(name),
+            "subscribed": subscribed,
+            "blocked": self.blocked_communities.iter().any(|community| community == name),
+            "counts": {
+                "id": id,
+                "community_id": id,
+                "subscribers": 0,
+                "posts": 0,
+                "comments": 0,
+                "published": TIMESTAMP,
+                "users_active_day": 0,
+                "users_active_week": 0,
+                "users_active_month": 0,
+                "users_active_half_year": 0,
+                "hot_rank": 0,
+            },
+        });
+    }
+
+    fn post_view(&self, ap_id: &str) -> Value {
+        let id = self.known_posts.iter().position(|post| post == ap_id).map(|index| index as i32 + 1).unwrap_or(0);
+        return json!({
+            "post": {
+                "id": id,
+                "name": format!("Post {}", id),
+                "creator_id": USER_ID,
+                "community_id": 1,
+                "removed": false,
+                "locked": false,
+                "published": TIMESTAMP,
+                "deleted": false,
+                "nsfw": false,
+                "ap_id": ap_id,
+                "local": false,
+                "language_id": 0,
+                "featured_community": false,
+                "featured_local": false,
+            },
+            "creator": self.me(),
+            "community": self.community("mock@mock.example"),
+            "creator_banned_from_community": false,
+            "counts": {
+                "id": id,
+                "post_id": id,
+                "comments": 0,
+                "score": 0,
+                "upvotes": 0,
+                "downvotes": 0,
+                "published": TIMESTAMP,
+                "newest_comment_time_necro": TIMESTAMP,
+                "newest_comment_time": TIMESTAMP,
+                "featured_community": false,
+                "featured_local": false,
+                "hot_rank": 0,
+                "hot_rank_active": 0,
+            },
+            "subscribed": "NotSubscribed",
+            "saved": self.saved_posts.iter().any(|post| post == ap_id),
+            "read": false,
+            "creator_blocked": false,
+            "unread_comments": 0,
+        });
+    }
+
+    fn handle(&mut self,
+        method: &Method,
+        path: &str,
+        query: &HashMap<String, String>,
+        body: &Value,
+        bearer: Option<&str>) -> Result<Value, (u16, &'static str)> {
+
+        match (method, path) {
+            (&Method::GET, "/api/v3/site") => return Ok(self.site_response(self.is_authorized(query, body, bearer))),
+            (&Method::POST, "/api/v3/user/login") => {
+                let username = body.get("username_or_email").and_then(|v| v.as_str()).unwrap_or("");
+                let password = body.get("password").and_then(|v| v.as_str()).unwrap_or("");
+                if username != self.username || password != self.password {
+                    return Err((400, "incorrect_login"));
+                }
+                return Ok(json!({ "jwt": self.jwt(), "registration_created": false, "verify_email_sent": false }));
+            },
+            (&Method::GET, "/api/v3/search") => {
+                // Communities are found by their name alone, as on a real instance
+                let q = query.get("q").cloned().unwrap_or_default();
+                let communities: Vec<Value> = self.known_communities.iter()
+                    .filter(|community| split_name(community).0.eq_ignore_ascii_case(&q))
+                    .map(|community| self.community_view(community))
+                    .collect();
+                return Ok(json!({ "type_": "Communities", "comments": [], "posts": [], "communities": communities, "users": [] }));
+            },
+            _ => {},
+        }
+
+        if !self.is_authorized(query, body, bearer) {
+            return Err((400, "not_logged_in"));
+        }
+
+        match (method, path) {
+            (&Method::GET, "/api/v3/community") => {
+                let name = query.get("name").cloned().unwrap_or_default();
+                if !self.known_communities.contains(&name) {
+                    return Err((404, "couldnt_find_community"));
+                }
+                return Ok(json!({ "community_view": self.community_view(&name), "moderators": [], "discussion_languages": [] }));
+            },
+            (&Method::POST, "/api/v3/community/block") => {
+                let name = self.community_name(body)?;
+                let block = body.get("block").and_then(|v| v.as_bool()).unwrap_or(false);
+                toggle(&mut self.blocked_communities, &name, block);
+                return Ok(json!({ "community_view": self.community_view(&name), "blocked": block }));
+            },
+            (&Method::POST, "/api/v3/community/follow") => {
+                let name = self.community_name(body)?;
+                let follow = body.get("follow").and_then(|v| v.as_bool()).unwrap_or(false);
+                toggle(&mut self.followed_communities, &name, follow);
+                return Ok(json!({ "community_view": self.community_view(&name), "discussion_languages": [] }));
+            },
+            (&Method::GET, "/api/v3/user") => {
+                if query.get("saved_only").map(|v| v == "true").unwrap_or(false) {
+                    let page = query.get("page").and_then(|v| v.parse::<usize>().ok()).unwrap_or(1);
+                    let limit = query.get("limit").and_then(|v| v.parse::<usize>().ok()).unwrap_or(10);
+                    let posts: Vec<Value> = self.saved_posts.iter()
+                        .skip((page - 1) * limit)
+                        .take(limit)
+                        .map(|ap_id| self.post_view(ap_id))
+                        .collect();
+                    return Ok(json!({ "person_view": { "person": self.me(), "counts": person_counts(USER_ID) }, "comments": [], "posts": posts, "moderates": [] }));
+                }
+
+                let name = query.get("username").cloned().unwrap_or_default();
+                if !self.known_users.contains(&name) {
+                    return Err((404, "couldnt_find_person"));
+                }
+                let person = self.person(&name);
+                let id = person["id"].as_i64().unwrap() as i32;
+                return Ok(json!({ "person_view": { "person": person, "counts": person_counts(id) }, "comments": [], "posts": [], "moderates": [] }));
+            },
+            (&Method::POST, "/api/v3/user/block") => {
+                let id = body.get("person_id").and_then(|v| v.as_i64()).unwrap_or(0) as usize;
+                let name = match id.checked_sub(2).and_then(|index| self.known_users.get(index)) {
+                    Some(name) => name.clone(),
+                    None => return Err((404, "couldnt_find_person")),
+                };
+                let block = body.get("block").and_then(|v| v.as_bool()).unwrap_or(false);
+                toggle(&mut self.blocked_users, &name, block);
+                let person = self.person(&name);
+                return Ok(json!({ "person_view": { "person": person, "counts": person_counts(id as i32) }, "blocked": block }));
+            },
+            (&Method::PUT, "/api/v3/user/save_user_settings") => {
+                if let Some(new_settings) = body.as_object() {
+                    for (name, value) in new_settings {
+                        if name != "auth" && !value.is_null() {
+                            self.settings.insert(name.clone(), value.clone());
+                        }
+                    }
+                }
+                if self.is_lemmy_019() {
+                    return Ok(json!({ "success": true }));
+                }
+                return Ok(json!({ "jwt": self.jwt(), "registration_created": false, "verify_email_sent": false }));
+            },
+            (&Method::GET, "/api/v3/federated_instances") if self.is_lemmy_019() => {
+                let linked: Vec<Value> = self.known_instances.iter().map(|domain| self.instance(domain)).collect();
+                return Ok(json!({ "federated_instances": { "linked": linked, "allowed": [], "blocked": [] } }));
+            },
+            (&Method::POST, "/api/v3/site/block") if self.is_lemmy_019() => {
+                let id = body.get("instance_id").and_then(|v| v.as_i64()).unwrap_or(0) as usize;
+                let domain = match id.checked_sub(1).and_then(|index| self.known_instances.get(index)) {
+                    Some(domain) => domain.clone(),
+                    None => return Err((404, "not_found")),
+                };
+                let block = body.get("block").and_then(|v| v.as_bool()).unwrap_or(false);
+                toggle(&mut self.blocked_instances, &domain, block);
+                return Ok(json!({ "blocked": block }));
+            },
+            (&Method::GET, "/api/v3/resolve_object") => {
+                let ap_id = query.get("q").cloned().unwrap_or_default();
+                if !self.known_posts.contains(&ap_id) {
+                    return Err((404, "couldnt_find_object"));
+                }
+                return Ok(json!({ "post": self.post_view(&ap_id) }));
+            },
+            (&Method::PUT, "/api/v3/post/save") => {
+                let id = body.get("post_id").and_then(|v| v.as_i64()).unwrap_or(0) as usize;
+                let ap_id = match id.checked_sub(1).and_then(|index| self.known_posts.get(index)) {
+                    Some(ap_id) => ap_id.clone(),
+                    None => return Err((404, "couldnt_find_post")),
+                };
+                let save = body.get("save").and_then(|v| v.as_bool()).unwrap_or(false);
+                toggle(&mut self.saved_posts, &ap_id, save);
+                return Ok(json!({ "post_view": self.post_view(&ap_id) }));
+            },
+            _ => return Err((404, "unknown_route")),
+        }
+    }
+
+    /// Stands in for pict-rs, which Lemmy proxies as-is: images are served as raw bytes and uploaded as multipart forms.
+    fn handle_image(&mut self,
+        method: &Method,
+        path: &str,
+        cookie: Option<&str>,
+        content_type: Option<&str>,
+        body: &[u8]) -> Option<(u16, Vec<u8>)> {
+
+        if method == Method::GET {
+            let file = path.strip_prefix("/pictrs/image/")?;
+            match self.images.get(file) {
+                Some(image) => return Some((200, image.clone())),
+                None => return Some((404, b"not found".to_vec())),
+            }
+        }
+        if method != Method::POST || path != "/pictrs/image" {
+            return None;
+        }
+
+        // Lemmy 0.18 only takes the JWT from a cookie here, 0.19 also reads the Authorization header
+        let jwt_cookie = format!("jwt={}", self.jwt());
+        if !cookie.map(|cookie| cookie.split("; ").any(|part| part == jwt_cookie)).unwrap_or(false) {
+            return Some((401, json!({ "msg": "not_logged_in", "files": null }).to_string().into_bytes()));
+        }
+
+        let image = match content_type.and_then(|content_type| multipart_file(content_type, body)) {
+            Some(image) => image,
+            None => return Some((400, json!({ "msg": "no_file", "files": null }).to_string().into_bytes())),
+        };
+        let file = format!("upload-{}.png", self.images.len() + 1);
+        self.images.insert(file.clone(), image);
+        return Some((200, json!({ "msg": "ok", "files": [{ "file": file, "delete_token": "token" }] }).to_string().into_bytes()));
+    }
+
+    fn community_name(&self, body: &Value) -> Result<String, (u16, &'static str)> {
+        let id = body.get("community_id").and_then(|v| v.as_i64()).unwrap_or(0) as usize;
+        match id.checked_sub(1).and_then(|index| self.known_communities.get(index)) {
+            Some(name) => return Ok(name.clone()),
+            None => return Err((404, "couldnt_find_community")),
+        }
+    }
+}
+
+// Pulls the contents of the first file out of a multipart/form-data body
+fn multipart_file(content_type: &str, body: &[u8]) -> Option<Vec<u8>> {
+    let boundary = content_type.split("boundary=").nth(1)?.trim_matches('"');
+    let closing = format!("\r\n--{}", boundary);
+    let start = find(body, b"\r\n\r\n")? + 4;
+    let end = start + find(&body[start..], closing.as_bytes())?;
+    return Some(body[start..end].to_vec());
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    return haystack.windows(needle.len()).position(|window| window == needle);
+}
+
+fn split_name(name: &str) -> (&str, &str) {
+    return name.split_once('@').unwrap_or((name, "mock.example"));
+}
+
+fn overlay(target: &mut Value, values: &serde_json::Map<String, Value>) {
+    if let Some(target_map) = target.as_object_mut() {
+        for (name, value) in values {
+            target_map.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+fn toggle(list: &mut Vec<String>, name: &str, present: bool) {
+    list.retain(|entry| entry != name);
+    if present {
+        list.push(name.to_string());
+    }
+}
+
+fn person_json(id: i32, actor_id: &str, name: &str) -> Value {
+    return json!({
+        "id": id,
+        "name": name,
+        "banned": false,
+        "published": TIMESTAMP,
+        "actor_id": actor_id,
+        "local": false,
+        "public_key": "",
+        "last_refreshed_at": TIMESTAMP,
+        "deleted": false,
+        "inbox_url": format!("{}/inbox", actor_id),
+        "admin": false,
+        "bot_account": false,
+        "instance_id": 1,
+    });
+}
+
+/// Reshapes a 0.18 response the way 0.19 changed it: UTC timestamps, admin only on the local user,
+/// float ranks and aggregates without their own id.
+fn as_lemmy_019(value: &mut Value) {
+    match value {
+        Value::String(text) if text == TIMESTAMP => *text = format!("{}.000000Z", TIMESTAMP),
+        Value::Array(values) => values.iter_mut().for_each(as_lemmy_019),
+        Value::Object(map) => {
+            if map.contains_key("actor_id") && map.contains_key("bot_account") {
+                map.remove("admin");
+            }
+            if let Some(Value::Object(counts)) = map.get_mut("counts") {
+                counts.remove("id");
+            }
+            for (name, value) in map.iter_mut() {
+                if name == "hot_rank" || name == "hot_rank_active" {
+                    *value = json!(value.as_f64().unwrap_or(0.0) + 0.25);
+                }
+                as_lemmy_019(value);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn person_counts(id: i32) -> Value {
+    return json!({
+        "id": id,
+        "person_id": id,
+        "post_count": 0,
+        "post_score": 0,
+        "comment_count": 0,
+        "comment_score": 0,
+    });
+}
+
+pub struct MockLemmy {
+    pub url: String,
+    pub state: Arc<Mutex<MockState>>,
+}
+
+impl MockLemmy {
+    /// Serves `state` on a random local port until the test's runtime shuts down.
+    pub async fn start(state: MockState) -> MockLemmy {
+        let state = Arc::new(Mutex::new(state));
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let connection_state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| respond(connection_state.clone(), request)))
+            }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        return MockLemmy {
+            url: url,
+            state: state,
+        };
+    }
+}
+
+async fn respond(state: Arc<Mutex<MockState>>, request: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let bearer = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .map(|token| token.to_string());
+    let cookie = request.headers()
+        .get(header::COOKIE)
+        .and_then(|cookie| cookie.to_str().ok())
+        .map(|cookie| cookie.to_string());
+    let content_type = request.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .map(|content_type| content_type.to_string());
+    let query: HashMap<String, String> = url::form_urlencoded::parse(request.uri().query().unwrap_or("").as_bytes())
+        .into_owned()
+        .collect();
+    let body_bytes = hyper::body::to_bytes(request.into_body()).await.unwrap_or_default();
+    let body: Value = serde_json::from_slice(&body_bytes).unwrap_or(Value::Null);
+
+    let mut state = state.lock().unwrap();
+    state.requests.push(format!("{} {}", method, path));
+
+    if let Some((status, image_body)) = state.handle_image(&method, &path, cookie.as_deref(), content_type.as_deref(), &body_bytes) {
+        let response = Response::builder()
+            .status(StatusCode::from_u16(status).unwrap())
+            .body(Body::from(image_body))
+            .unwrap();
+        return Ok(response);
+    }
+
+    let mut forced_failure = None;
+    if let Some(failure) = state.failures.get_mut(&path) {
+        if failure.remaining > 0 {
+            failure.remaining -= 1;
+            forced_failure = Some(failure.status);
+        }
+    }
+
+    // Forced 400s look like Lemmy's rate limiting, and all failures ask to be retried straight away so tests stay fast
+    let (status, response_body) = match forced_failure {
+        Some(400) => (400, json!({ "error": "rate_limit_error" })),
+        Some(status) => (status, json!({ "error": "mock_failure" })),
+        None => match state.handle(&method, &path, &query, &body, bearer.as_deref()) {
+            Ok(mut response_body) => {
+                if state.is_lemmy_019() {
+                    as_lemmy_019(&mut response_body);
+                }
+                (200, response_body)
+            },
+            Err((status, error)) => (status, json!({ "error": error })),
+        },
+    };
+
+    let mut response = Response::builder()
+        .status(StatusCode::from_u16(status).unwrap())
+        .header("content-type", "application/json");
+    if forced_failure.is_some() {
+        response = response.header("retry-after", "0");
+    }
+    let response = response.body(Body::from(response_body.to_string())).unwrap();
+    return Ok(response);
+}
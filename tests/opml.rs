@@ -0,0 +1,50 @@
+use lasim::opml;
+
+#[test]
+fn feed_url_is_derived_from_community_name() {
+    assert_eq!(opml::community_feed_url("rust@lemmy.ml"), Ok("https://lemmy.ml/feeds/c/rust.xml".to_string()));
+    assert_eq!(opml::community_from_feed_url("https://lemmy.ml/feeds/c/rust.xml?sort=Hot"), Some("rust@lemmy.ml".to_string()));
+    assert_eq!(opml::community_from_feed_url("http://127.0.0.1:8536/feeds/c/local.xml"), Some("local@127.0.0.1:8536".to_string()));
+    assert_eq!(opml::community_from_feed_url("https://lemmy.ml/feeds/u/someone.xml"), None);
+    assert!(opml::community_feed_url("not a community").is_err());
+}
+
+#[test]
+fn exported_follows_import_again() {
+    let communities = vec!["rust@lemmy.ml".to_string(), "memes@lemmy.world".to_string()];
+    let opml_export = opml::export_opml("Follows & more", &communities);
+    assert!(opml_export.skipped.is_empty());
+
+    let opml_import = opml::import_opml(&opml_export.opml);
+    assert_eq!(opml_import.communities, communities);
+    assert!(opml_import.skipped.is_empty());
+}
+
+#[test]
+fn export_skips_malformed_entries() {
+    let communities = vec!["rust@lemmy.ml".to_string(), "not a community".to_string(), "memes@lemmy.world".to_string()];
+    let opml_export = opml::export_opml("Follows", &communities);
+    assert_eq!(opml_export.exported, vec!["rust@lemmy.ml", "memes@lemmy.world"]);
+    assert_eq!(opml_export.skipped, vec!["not a community"]);
+
+    let opml_import = opml::import_opml(&opml_export.opml);
+    assert_eq!(opml_import.communities, vec!["rust@lemmy.ml", "memes@lemmy.world"]);
+}
+
+#[test]
+fn import_skips_other_feeds() {
+    let feed_list = r#"<?xml version="1.0"?>
+<opml version="1.0">
+  <body>
+    <outline text="Lemmy">
+      <outline type="rss" text="Rust" htmlUrl="https://lemmy.ml/c/rust" xmlurl='https://lemmy.ml/feeds/c/rust.xml'/>
+      <outline type="rss" text="Rust again" xmlUrl="https://lemmy.ml/feeds/c/rust.xml"/>
+    </outline>
+    <outline type="rss" text="Blog" xmlUrl="https://example.com/feed.xml?a=1&amp;b=2"/>
+  </body>
+</opml>"#;
+
+    let opml_import = opml::import_opml(feed_list);
+    assert_eq!(opml_import.communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(opml_import.skipped, vec!["https://example.com/feed.xml?a=1&b=2"]);
+}
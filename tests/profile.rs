@@ -0,0 +1,27 @@
+use lasim::profile;
+
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+#[test]
+fn name_at_host_accepts_users_and_communities() {
+    assert_eq!(profile::validate_name_at_host("rust@lemmy.ml"), Ok(()));
+    assert_eq!(profile::validate_name_at_host("Some_User2@sh.itjust.works"), Ok(()));
+    assert_eq!(profile::validate_name_at_host("local@127.0.0.1:8536"), Ok(()));
+    assert_eq!(profile::validate_name_at_host("first.last@mastodon.social"), Ok(()));
+    assert_eq!(profile::validate_name_at_host("some-magazine@kbin.social"), Ok(()));
+}
+
+#[test]
+fn name_at_host_rejects_malformed_entries() {
+    for entry in ["rust", "@lemmy.ml", "rust@", "!rust@lemmy.ml", "rust@lemmy..ml", "rust@-lemmy.ml", "rust@lemmy.ml:port", "rust@lemmy.ml/c/rust"] {
+        assert!(profile::validate_name_at_host(entry).is_err(), "{} should be rejected", entry);
+    }
+}
+
+#[test]
+fn timestamps_are_formatted_as_utc() {
+    assert_eq!(profile::format_utc_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(profile::format_utc_timestamp(UNIX_EPOCH + Duration::from_secs(951782400)), "2000-02-29T00:00:00Z");
+    assert_eq!(profile::format_utc_timestamp(UNIX_EPOCH + Duration::from_secs(1709210096)), "2024-02-29T12:34:56Z");
+}
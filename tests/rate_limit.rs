@@ -0,0 +1,77 @@
+use lasim::lemmy::rate_limit::RateLimiter;
+
+use tokio::time::Instant;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+fn assert_close(actual: Duration, expected: Duration) {
+    assert!((actual.as_secs_f64() - expected.as_secs_f64()).abs() < 0.001, "got {:?}, expected {:?}", actual, expected);
+}
+
+fn assert_waited(start: Instant, expected: Duration) {
+    // The paused clock jumps straight to each timer, which fires on the next millisecond
+    let waited = start.elapsed();
+    assert!(waited >= expected && waited <= expected + Duration::from_millis(2), "waited {:?}, expected {:?}", waited, expected);
+}
+
+#[tokio::test(start_paused = true)]
+async fn bucket_starts_full_and_refills() {
+    let rate_limiter = RateLimiter::new(2, 1);
+
+    let start = Instant::now();
+    rate_limiter.acquire().await;
+    rate_limiter.acquire().await;
+    assert_waited(start, Duration::ZERO);
+    rate_limiter.acquire().await;
+    assert_waited(start, Duration::from_millis(500));
+
+    // A whole period refills the bucket, but never past its capacity
+    tokio::time::advance(Duration::from_secs(5)).await;
+    let start = Instant::now();
+    rate_limiter.acquire().await;
+    rate_limiter.acquire().await;
+    assert_waited(start, Duration::ZERO);
+    rate_limiter.acquire().await;
+    assert_waited(start, Duration::from_millis(500));
+}
+
+#[test]
+fn estimate_counts_requests_beyond_capacity() {
+    let rate_limiter = RateLimiter::new(10, 60);
+    assert_eq!(rate_limiter.estimate(10), Duration::ZERO);
+    assert_close(rate_limiter.estimate(16), Duration::from_secs(36));
+
+    // Nonsense limits are treated as one request per second
+    let rate_limiter = RateLimiter::new(0, 0);
+    assert_close(rate_limiter.estimate(3), Duration::from_secs(2));
+}
+
+#[test]
+fn concurrent_requests_are_capped() {
+    assert_eq!(RateLimiter::new(999, 1).max_concurrent_requests(), 8);
+    assert_eq!(RateLimiter::new(8, 1).max_concurrent_requests(), 8);
+    assert_eq!(RateLimiter::new(3, 1).max_concurrent_requests(), 3);
+    assert_eq!(RateLimiter::new(0, 1).max_concurrent_requests(), 1);
+    assert_eq!(RateLimiter::new(-5, 0).max_concurrent_requests(), 1);
+}
+
+#[tokio::test(start_paused = true)]
+async fn waiting_does_not_block_other_tasks() {
+    let rate_limiter = Arc::new(RateLimiter::new(1, 10));
+    rate_limiter.acquire().await;
+
+    let start = Instant::now();
+    let waiting_limiter = rate_limiter.clone();
+    let waiting = tokio::spawn(async move { waiting_limiter.acquire().await });
+    tokio::task::yield_now().await;
+
+    // On this single threaded runtime, a blocking wait would hold up this task until the wait was over
+    let other = tokio::spawn(async { return 42; });
+    assert_eq!(other.await.unwrap(), 42);
+    assert!(!waiting.is_finished());
+    assert_waited(start, Duration::ZERO);
+
+    waiting.await.unwrap();
+    assert_waited(start, Duration::from_secs(10));
+}
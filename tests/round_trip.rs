@@ -0,0 +1,673 @@
+mod mock_lemmy;
+
+use lasim::engine;
+use lasim::engine::Cancellation;
+use lasim::engine::GlobalSettings;
+use lasim::engine::ProcessingInstruction;
+use mock_lemmy::MockLemmy;
+use mock_lemmy::MockState;
+use url::Url;
+
+use std::sync::Once;
+
+const PASSWORD: &str = "hunter22";
+
+static DATA_DIRECTORY: Once = Once::new();
+
+// Every test shares one data directory, so each test uses its own usernames to keep profiles apart
+fn use_test_data_directory() {
+    DATA_DIRECTORY.call_once(|| {
+        let directory = std::env::temp_dir().join(format!("lasim-test-{}", std::process::id()));
+        std::env::set_var("LASIM_DATA_DIR", directory);
+    });
+}
+
+fn instruction(mock: &MockLemmy, username: &str, global_settings: GlobalSettings, profile_name: &str) -> ProcessingInstruction {
+    return ProcessingInstruction {
+        instance: mock.url.clone(),
+        username: username.to_string(),
+        password: PASSWORD.to_string(),
+        two_factor_token: String::new(),
+        global_settings: global_settings,
+        profile_name: profile_name.to_string(),
+    };
+}
+
+fn upload_settings() -> GlobalSettings {
+    let mut global_settings = GlobalSettings::default();
+    global_settings.confirm_uploads = false;
+    global_settings.upload_user_saved_posts = true;
+    return global_settings;
+}
+
+fn source_state(username: &str) -> MockState {
+    let mut state = MockState::new("old.example", username, PASSWORD);
+    state.known_communities = vec!["rust@lemmy.ml".to_string(), "memes@lemmy.world".to_string()];
+    state.known_users = vec!["spammer@lemmy.world".to_string()];
+    state.known_posts = vec!["https://lemmy.ml/post/1".to_string()];
+    state.followed_communities = vec!["rust@lemmy.ml".to_string()];
+    state.blocked_communities = vec!["memes@lemmy.world".to_string()];
+    state.blocked_users = vec!["spammer@lemmy.world".to_string()];
+    state.saved_posts = vec!["https://lemmy.ml/post/1".to_string()];
+    state.settings.insert("show_nsfw".to_string(), true.into());
+    state.settings.insert("default_sort_type".to_string(), "New".into());
+    return state;
+}
+
+fn target_state(username: &str) -> MockState {
+    let mut state = MockState::new("new.example", username, PASSWORD);
+    state.known_communities = vec![
+        "rust@lemmy.ml".to_string(),
+        "memes@lemmy.world".to_string(),
+        "linux@lemmy.ml".to_string(),
+    ];
+    state.known_users = vec!["spammer@lemmy.world".to_string()];
+    state.known_posts = vec!["https://lemmy.ml/post/1".to_string()];
+    return state;
+}
+
+async fn download(username: &str, state: MockState) -> String {
+    use_test_data_directory();
+    let source = MockLemmy::start(state).await;
+    let result = engine::process_download(instruction(&source, username, GlobalSettings::default(), ""), |_| {}, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    return account_profile_name(&source, username);
+}
+
+fn account_profile_name(mock: &MockLemmy, username: &str) -> String {
+    return lasim::storage::profile_name(username, &Url::parse(&mock.url).unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn download_writes_profile() {
+    let profile_name = download("download_old", source_state("download_old")).await;
+
+    let profile = engine::read_profile(&profile_name, |_| {}).unwrap();
+    assert_eq!(profile.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(profile.blocked_communities, vec!["memes@lemmy.world"]);
+    assert_eq!(profile.blocked_users, vec!["spammer@lemmy.world"]);
+    assert_eq!(profile.saved_posts, vec!["https://lemmy.ml/post/1"]);
+    assert!(profile.profile_settings.show_nsfw);
+    assert_eq!(profile.profile_settings.default_sort_type, "New");
+
+    let metadata = profile.metadata.unwrap();
+    assert_eq!(metadata.source_actor_id, "https://old.example/u/download_old");
+    assert_eq!(metadata.backend_version, "0.18.3");
+    assert_eq!(metadata.lasim_version, env!("CARGO_PKG_VERSION"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_warns_about_profile_from_same_account() {
+    let profile_name = download("same_account", source_state("same_account")).await;
+    let target = MockLemmy::start(source_state("same_account")).await;
+
+    let mut log = Vec::new();
+    let result = engine::process_diff(instruction(&target, "same_account", upload_settings(), &profile_name), |text| log.push(text)).await;
+    assert_eq!(result, Ok(()));
+    assert!(log.iter().any(|line| line.starts_with("Profile was downloaded from https://old.example/u/same_account")));
+    assert!(log.iter().any(|line| line.starts_with(&format!("WARNING: Profile {} was downloaded from this same account", profile_name))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_applies_profile() {
+    let profile_name = download("upload_old", source_state("upload_old")).await;
+    let target = MockLemmy::start(target_state("upload_new")).await;
+
+    let result = engine::process_upload(instruction(&target, "upload_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(state.blocked_communities, vec!["memes@lemmy.world"]);
+    assert_eq!(state.blocked_users, vec!["spammer@lemmy.world"]);
+    assert_eq!(state.saved_posts, vec!["https://lemmy.ml/post/1"]);
+    assert_eq!(state.settings["show_nsfw"], true);
+    assert_eq!(state.settings["default_sort_type"], "New");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn identity_fields_are_only_uploaded_when_enabled() {
+    let mut state = source_state("identity_old");
+    state.settings.insert("display_name".to_string(), "Old Me".into());
+    state.settings.insert("bio".to_string(), "Hello from the old account".into());
+    state.settings.insert("matrix_user_id".to_string(), "@old:matrix.org".into());
+    let profile_name = download("identity_old", state).await;
+
+    let profile = engine::read_profile(&profile_name, |_| {}).unwrap();
+    assert_eq!(profile.profile_settings.display_name, Some("Old Me".to_string()));
+    assert_eq!(profile.profile_settings.bio, Some("Hello from the old account".to_string()));
+    assert_eq!(profile.profile_settings.matrix_user_id, Some("@old:matrix.org".to_string()));
+
+    let skipped = MockLemmy::start(target_state("identity_skipped")).await;
+    let result = engine::process_upload(instruction(&skipped, "identity_skipped", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    for field in ["display_name", "bio", "matrix_user_id"] {
+        assert!(!skipped.state.lock().unwrap().settings.contains_key(field), "{} should not be uploaded", field);
+    }
+
+    let mut global_settings = upload_settings();
+    global_settings.upload_display_name = true;
+    global_settings.upload_bio = true;
+    global_settings.upload_matrix_user_id = true;
+    let target = MockLemmy::start(target_state("identity_new")).await;
+    let result = engine::process_upload(instruction(&target, "identity_new", global_settings, &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.settings["display_name"], "Old Me");
+    assert_eq!(state.settings["bio"], "Hello from the old account");
+    assert_eq!(state.settings["matrix_user_id"], "@old:matrix.org");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn avatar_is_uploaded_to_new_instance() {
+    use_test_data_directory();
+    let image = b"not really a png".to_vec();
+    let source = MockLemmy::start(source_state("avatar_old")).await;
+    {
+        let mut state = source.state.lock().unwrap();
+        state.images.insert("avatar.png".to_string(), image.clone());
+        let avatar_url = format!("{}/pictrs/image/avatar.png", source.url);
+        state.settings.insert("avatar".to_string(), avatar_url.into());
+    }
+    let result = engine::process_download(instruction(&source, "avatar_old", GlobalSettings::default(), ""), |_| {}, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    let profile_name = account_profile_name(&source, "avatar_old");
+    let profile_directory = lasim::storage::get_profile_directory(&profile_name).unwrap();
+    assert_eq!(std::fs::read(profile_directory.join("profile_avatar.png")).unwrap(), image);
+
+    // The local copy is uploaded even once the old instance no longer serves the image
+    source.state.lock().unwrap().images.clear();
+    let target = MockLemmy::start(target_state("avatar_new")).await;
+    let result = engine::process_upload(instruction(&target, "avatar_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.images.get("upload-1.png"), Some(&image));
+    assert_eq!(state.settings["avatar"], format!("{}/pictrs/image/upload-1.png", target.url));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn secrets_stay_out_of_logs_and_files() {
+    let mut global_settings = upload_settings();
+    global_settings.write_api_profiles = true;
+    use_test_data_directory();
+    let source = MockLemmy::start(source_state("secret_old")).await;
+    let mut log = Vec::new();
+    let result = engine::process_download(instruction(&source, "secret_old", global_settings.clone(), ""), |text| log.push(text), &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let target = MockLemmy::start(target_state("secret_new")).await;
+    let source_profile_name = account_profile_name(&source, "secret_old");
+    let result = engine::process_upload(instruction(&target, "secret_new", global_settings, &source_profile_name), |text| log.push(text), |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let secrets = [PASSWORD, "mock-jwt-", "@mail.example"];
+    for line in &log {
+        assert!(!secrets.iter().any(|secret| line.contains(secret)), "log line leaks a secret: {}", line);
+    }
+    for profile_name in [source_profile_name.clone(), account_profile_name(&target, "secret_new")] {
+        let profile_directory = lasim::storage::get_profile_directory(&profile_name).unwrap();
+        let mut file_count = 0;
+        for dir_entry in std::fs::read_dir(&profile_directory).unwrap().flatten() {
+            let contents = std::fs::read_to_string(dir_entry.path()).unwrap();
+            assert!(!secrets.iter().any(|secret| contents.contains(secret)), "{} leaks a secret", dir_entry.path().display());
+            file_count += 1;
+        }
+        assert!(file_count > 0);
+    }
+
+    let api_profile = std::fs::read_to_string(lasim::storage::get_profile_directory(&source_profile_name).unwrap().join("api_profile_download.json")).unwrap();
+    assert!(api_profile.contains("REDACTED"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_is_additive_without_sync_removals() {
+    let profile_name = download("additive_old", source_state("additive_old")).await;
+    let mut state = target_state("additive_new");
+    state.followed_communities = vec!["linux@lemmy.ml".to_string()];
+    let target = MockLemmy::start(state).await;
+
+    let result = engine::process_upload(instruction(&target, "additive_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["linux@lemmy.ml", "rust@lemmy.ml"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_with_sync_removals_matches_profile() {
+    let profile_name = download("sync_old", source_state("sync_old")).await;
+    let mut state = target_state("sync_new");
+    state.followed_communities = vec!["linux@lemmy.ml".to_string()];
+    state.blocked_communities = vec!["rust@lemmy.ml".to_string(), "memes@lemmy.world".to_string()];
+    let target = MockLemmy::start(state).await;
+
+    let mut global_settings = upload_settings();
+    global_settings.sync_removals = true;
+    let result = engine::process_upload(instruction(&target, "sync_new", global_settings, &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(state.blocked_communities, vec!["memes@lemmy.world"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_refuses_profile_with_errors() {
+    let profile_name = download("invalid_old", source_state("invalid_old")).await;
+    let mut profile = engine::read_profile(&profile_name, |_| {}).unwrap();
+    profile.profile_settings.default_sort_type = "Sideways".to_string();
+    profile.followed_communities.push("not a community".to_string());
+    engine::write_profile(&lasim::storage::get_profile_directory(&profile_name).unwrap(), &profile, |_| {}).unwrap();
+    let target = MockLemmy::start(target_state("invalid_new")).await;
+
+    let mut log = Vec::new();
+    let result = engine::process_upload(instruction(&target, "invalid_new", upload_settings(), &profile_name), |text| log.push(text), |_| true, &Cancellation::new()).await;
+    assert!(result.unwrap_err().contains("has 1 error(s) and 1 warning(s)"));
+    assert!(log.contains(&"ERROR: profile_settings.default_sort_type - Sideways is not a sort type Lemmy knows".to_string()));
+
+    let state = target.state.lock().unwrap();
+    assert!(state.followed_communities.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn federated_names_upload_and_malformed_entries_are_skipped() {
+    let federated_user = "first.last@mastodon.social".to_string();
+    let federated_community = "some-magazine@kbin.social".to_string();
+    let mut state = source_state("federated_old");
+    state.known_users.push(federated_user.clone());
+    state.known_communities.push(federated_community.clone());
+    state.blocked_users.push(federated_user.clone());
+    state.followed_communities.push(federated_community.clone());
+    let profile_name = download("federated_old", state).await;
+    let mut profile = engine::read_profile(&profile_name, |_| {}).unwrap();
+    profile.followed_communities.push("not a community".to_string());
+    engine::write_profile(&lasim::storage::get_profile_directory(&profile_name).unwrap(), &profile, |_| {}).unwrap();
+
+    let mut state = target_state("federated_new");
+    state.known_users.push(federated_user.clone());
+    state.known_communities.push(federated_community.clone());
+    let target = MockLemmy::start(state).await;
+
+    let mut log = Vec::new();
+    let result = engine::process_upload(instruction(&target, "federated_new", upload_settings(), &profile_name), |text| log.push(text), |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    assert!(log.contains(&"WARNING: followed_communities - not a community is not written as name@host, it is skipped when uploading".to_string()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities.len(), 2);
+    assert!(state.followed_communities.contains(&federated_community));
+    assert_eq!(state.blocked_users.len(), 2);
+    assert!(state.blocked_users.contains(&federated_user));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_skips_unknown_communities() {
+    let mut state = source_state("unknown_old");
+    state.known_communities.push("gone@dead.example".to_string());
+    state.followed_communities.push("gone@dead.example".to_string());
+    let profile_name = download("unknown_old", state).await;
+    let target = MockLemmy::start(target_state("unknown_new")).await;
+
+    let result = engine::process_upload(instruction(&target, "unknown_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert!(result.is_err());
+
+    // Everything else is still applied
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(state.blocked_users, vec!["spammer@lemmy.world"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_declined_changes_nothing() {
+    let profile_name = download("declined_old", source_state("declined_old")).await;
+    let target = MockLemmy::start(target_state("declined_new")).await;
+
+    let mut global_settings = upload_settings();
+    global_settings.confirm_uploads = true;
+    let result = engine::process_upload(instruction(&target, "declined_new", global_settings, &profile_name), |_| {}, |_| false, &Cancellation::new()).await;
+    assert!(result.is_err());
+
+    let state = target.state.lock().unwrap();
+    assert!(state.followed_communities.is_empty());
+    assert!(state.blocked_users.is_empty());
+    assert_eq!(state.settings["show_nsfw"], false);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn diff_changes_nothing() {
+    let profile_name = download("diff_old", source_state("diff_old")).await;
+    let target = MockLemmy::start(target_state("diff_new")).await;
+
+    let mut log: Vec<String> = vec![];
+    let result = engine::process_diff(instruction(&target, "diff_new", upload_settings(), &profile_name), |text| log.push(text)).await;
+    assert_eq!(result, Ok(()));
+    assert!(log.contains(&"    rust@lemmy.ml".to_string()));
+
+    let state = target.state.lock().unwrap();
+    assert!(state.followed_communities.is_empty());
+    assert_eq!(state.count_requests("PUT /api/v3/user/save_user_settings"), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merged_profile_uploads_union() {
+    let first_profile = download("merge_first", source_state("merge_first")).await;
+    let mut state = MockState::new("other.example", "merge_second", PASSWORD);
+    state.known_communities = vec!["linux@lemmy.ml".to_string(), "rust@lemmy.ml".to_string()];
+    state.followed_communities = vec!["linux@lemmy.ml".to_string(), "rust@lemmy.ml".to_string()];
+    state.settings.insert("default_sort_type".to_string(), "TopDay".into());
+    let second_profile = download("merge_second", state).await;
+
+    let result = engine::process_merge(&[first_profile.clone(), second_profile.clone()], &second_profile, "merge_combined", |_| {});
+    assert_eq!(result, Ok(()));
+
+    for merged_profile_name in ["", "..", "C:x", "merge\tcombined", "Working Directory"] {
+        assert!(engine::process_merge(&[first_profile.clone(), second_profile.clone()], &second_profile, merged_profile_name, |_| {}).is_err(), "{} should be rejected", merged_profile_name);
+    }
+
+    let merged_profile = engine::read_profile("merge_combined", |_| {}).unwrap();
+    assert_eq!(merged_profile.followed_communities, vec!["rust@lemmy.ml", "linux@lemmy.ml"]);
+    assert_eq!(merged_profile.blocked_communities, vec!["memes@lemmy.world"]);
+    assert_eq!(merged_profile.profile_settings.default_sort_type, "TopDay");
+
+    let target = MockLemmy::start(target_state("merge_new")).await;
+    let result = engine::process_upload(instruction(&target, "merge_new", upload_settings(), "merge_combined"), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml", "linux@lemmy.ml"]);
+    assert_eq!(state.blocked_users, vec!["spammer@lemmy.world"]);
+    assert_eq!(state.settings["default_sort_type"], "TopDay");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_rejects_replacing_a_merged_profile() {
+    let profile_name = download("merge_self", source_state("merge_self")).await;
+
+    let result = engine::process_merge(&[profile_name.clone(), profile_name.clone()], &profile_name, &profile_name, |_| {});
+    assert!(result.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compare_lists_differences_between_profiles() {
+    let from_profile = download("compare_from", source_state("compare_from")).await;
+    let mut state = source_state("compare_to");
+    state.followed_communities = vec!["memes@lemmy.world".to_string()];
+    state.blocked_communities.clear();
+    state.settings.insert("show_nsfw".to_string(), false.into());
+    let to_profile = download("compare_to", state).await;
+
+    let mut log: Vec<String> = vec![];
+    let result = engine::process_compare(&from_profile, &to_profile, |text| log.push(text));
+    assert_eq!(result, Ok(()));
+
+    assert!(log.contains(&"Followed communities added (1):".to_string()));
+    assert!(log.contains(&"    memes@lemmy.world".to_string()));
+    assert!(log.contains(&"Followed communities removed (1):".to_string()));
+    assert!(log.contains(&"    rust@lemmy.ml".to_string()));
+    assert!(log.contains(&"Blocked communities removed (1):".to_string()));
+    assert!(log.contains(&"    show_nsfw: true -> false".to_string()));
+    assert!(!log.iter().any(|line| line.starts_with("Blocked users")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn compare_account_changes_nothing() {
+    let profile_name = download("compare_account_old", source_state("compare_account_old")).await;
+    let target = MockLemmy::start(target_state("compare_account_new")).await;
+
+    let mut log: Vec<String> = vec![];
+    let result = engine::process_compare_account(instruction(&target, "compare_account_new", upload_settings(), &profile_name), |text| log.push(text)).await;
+    assert_eq!(result, Ok(()));
+    assert!(log.contains(&"Followed communities removed (1):".to_string()));
+
+    let state = target.state.lock().unwrap();
+    assert!(state.followed_communities.is_empty());
+    assert_eq!(state.count_requests("POST /api/v3/community/follow"), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reddit_import_adds_mapped_and_found_communities() {
+    let profile_name = download("reddit_new", source_state("reddit_new")).await;
+    let search = MockLemmy::start(target_state("reddit_search")).await;
+
+    let export_directory = std::env::temp_dir().join(format!("lasim-reddit-{}", std::process::id()));
+    std::fs::create_dir_all(&export_directory).unwrap();
+    let subreddits_path = export_directory.join(lasim::reddit::SUBSCRIBED_SUBREDDITS_FILENAME);
+    std::fs::write(&subreddits_path, "subreddit\nmemes\nLinux\nrust\nsomethingelse\n").unwrap();
+    let mapping_path = export_directory.join("mapping.csv");
+    std::fs::write(&mapping_path, "subreddit,community\n# Memes moved\nr/Memes,!memes@lemmy.world\n").unwrap();
+
+    let mut log: Vec<String> = vec![];
+    let result = engine::process_reddit_import(&subreddits_path, Some(&mapping_path), Some(&search.url), &profile_name, |text| log.push(text)).await;
+    assert_eq!(result, Ok(()));
+
+    let profile = engine::read_profile(&profile_name, |_| {}).unwrap();
+    assert_eq!(profile.followed_communities, vec!["rust@lemmy.ml", "memes@lemmy.world", "linux@lemmy.ml"]);
+    assert!(log.contains(&"No community found for (1):".to_string()));
+    assert!(log.contains(&"    r/somethingelse".to_string()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_retries_rate_limited_requests() {
+    let profile_name = download("retry_old", source_state("retry_old")).await;
+    let mut state = target_state("retry_new");
+    state.fail("/api/v3/community/follow", 400, 2);
+    state.fail("/api/v3/user", 503, 1);
+    let target = MockLemmy::start(state).await;
+
+    let result = engine::process_upload(instruction(&target, "retry_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(state.blocked_users, vec!["spammer@lemmy.world"]);
+    assert_eq!(state.count_requests("POST /api/v3/community/follow"), 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_reports_entries_that_exhaust_retries() {
+    let profile_name = download("exhausted_old", source_state("exhausted_old")).await;
+    let mut state = target_state("exhausted_new");
+    state.fail("/api/v3/community/follow", 429, 100);
+    let target = MockLemmy::start(state).await;
+
+    let mut log: Vec<String> = vec![];
+    let result = engine::process_upload(instruction(&target, "exhausted_new", upload_settings(), &profile_name), |text| log.push(text), |_| true, &Cancellation::new()).await;
+    assert!(result.is_err());
+    assert!(log.contains(&"    follow community rust@lemmy.ml".to_string()));
+
+    // The other steps are unaffected
+    let state = target.state.lock().unwrap();
+    assert!(state.followed_communities.is_empty());
+    assert_eq!(state.blocked_users, vec!["spammer@lemmy.world"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_resumes_from_journal() {
+    let profile_name = download("resume_old", source_state("resume_old")).await;
+    let mut state = target_state("resume_new");
+    state.fail("/api/v3/community/follow", 403, 1);
+    let target = MockLemmy::start(state).await;
+    let journal_path = lasim::storage::get_profile_directory(&account_profile_name(&target, "resume_new")).unwrap().join(lasim::journal::JOURNAL_FILENAME);
+
+    let result = engine::process_upload(instruction(&target, "resume_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert!(result.is_err());
+    assert!(journal_path.exists());
+
+    // Only the failed follow is tried again
+    let result = engine::process_upload(instruction(&target, "resume_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    assert!(!journal_path.exists());
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(state.count_requests("POST /api/v3/community/follow"), 2);
+    assert_eq!(state.count_requests("POST /api/v3/user/block"), 1);
+    assert_eq!(state.count_requests("PUT /api/v3/user/save_user_settings"), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn journal_is_discarded_when_settings_change() {
+    let profile_name = download("restart_old", source_state("restart_old")).await;
+    let mut state = target_state("restart_new");
+    state.fail("/api/v3/community/follow", 403, 1);
+    let target = MockLemmy::start(state).await;
+
+    let result = engine::process_upload(instruction(&target, "restart_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert!(result.is_err());
+
+    let mut global_settings = upload_settings();
+    global_settings.upload_user_blocks = false;
+    let mut log: Vec<String> = vec![];
+    let result = engine::process_upload(instruction(&target, "restart_new", global_settings, &profile_name), |text| log.push(text), |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    assert!(log.iter().any(|line| line.starts_with("Starting over")));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn undo_reverts_last_upload() {
+    let profile_name = download("undo_old", source_state("undo_old")).await;
+    let mut state = target_state("undo_new");
+    state.followed_communities = vec!["linux@lemmy.ml".to_string()];
+    let target = MockLemmy::start(state).await;
+    let last_upload_path = lasim::storage::get_profile_directory(&account_profile_name(&target, "undo_new")).unwrap().join(lasim::journal::LAST_UPLOAD_FILENAME);
+
+    let result = engine::process_upload(instruction(&target, "undo_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    assert!(last_upload_path.exists());
+
+    let result = engine::process_undo(instruction(&target, "undo_new", upload_settings(), ""), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    assert!(!last_upload_path.exists());
+
+    // Only what the upload changed is reverted
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["linux@lemmy.ml"]);
+    assert!(state.blocked_communities.is_empty());
+    assert!(state.blocked_users.is_empty());
+    assert!(state.saved_posts.is_empty());
+    assert_eq!(state.settings["show_nsfw"], false);
+    drop(state);
+
+    let result = engine::process_undo(instruction(&target, "undo_new", upload_settings(), ""), |_| {}, |_| true, &Cancellation::new()).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_cancelled_before_applying_changes_nothing() {
+    let profile_name = download("cancel_old", source_state("cancel_old")).await;
+    let target = MockLemmy::start(target_state("cancel_new")).await;
+
+    let mut global_settings = upload_settings();
+    global_settings.confirm_uploads = true;
+    let cancellation = Cancellation::new();
+    let confirm = |_: &lasim::profile::ProfileChanges| {
+        cancellation.cancel();
+        return true;
+    };
+    let result = engine::process_upload(instruction(&target, "cancel_new", global_settings, &profile_name), |_| {}, confirm, &cancellation).await;
+    assert_eq!(result, Err("Upload cancelled. No changes were made.".to_string()));
+
+    let state = target.state.lock().unwrap();
+    assert!(state.followed_communities.is_empty());
+    assert_eq!(state.count_requests("PUT /api/v3/user/save_user_settings"), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn cancelled_step_reports_entries_not_applied() {
+    use_test_data_directory();
+    let target = MockLemmy::start(target_state("cancel_step")).await;
+    let (api, jwt_token) = engine::login(&instruction(&target, "cancel_step", upload_settings(), ""), |_| {}).await.unwrap();
+
+    let cancellation = Cancellation::new();
+    cancellation.cancel();
+    let communities = vec!["rust@lemmy.ml".to_string(), "linux@lemmy.ml".to_string()];
+    let report = engine::follow_communities(&api, &jwt_token, &cancellation, None, |_| {}, &communities, true).await;
+    assert!(report.applied.is_empty());
+    assert_eq!(report.not_applied.len(), 2);
+    assert!(report.not_applied.contains(&"follow community linux@lemmy.ml".to_string()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.count_requests("POST /api/v3/community/follow"), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn login_with_wrong_password_fails() {
+    use_test_data_directory();
+    let mut state = source_state("wrong_password");
+    state.password = "something else".to_string();
+    let source = MockLemmy::start(state).await;
+
+    let result = engine::process_download(instruction(&source, "wrong_password", GlobalSettings::default(), ""), |_| {}, &Cancellation::new()).await;
+    assert!(result.unwrap_err().contains("Failed Login"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn round_trip_between_lemmy_018_and_019() {
+    let mut state = source_state("lemmy019_old");
+    state.version = "0.19.3".to_string();
+    let profile_name = download("lemmy019_old", state).await;
+
+    let profile = engine::read_profile(&profile_name, |_| {}).unwrap();
+    assert_eq!(profile.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(profile.saved_posts, vec!["https://lemmy.ml/post/1"]);
+    assert_eq!(profile.profile_settings.default_sort_type, "New");
+
+    let mut state = target_state("lemmy019_new");
+    state.version = "0.19.3".to_string();
+    let target = MockLemmy::start(state).await;
+
+    let result = engine::process_upload(instruction(&target, "lemmy019_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = target.state.lock().unwrap();
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+    assert_eq!(state.blocked_communities, vec!["memes@lemmy.world"]);
+    assert_eq!(state.blocked_users, vec!["spammer@lemmy.world"]);
+    assert_eq!(state.saved_posts, vec!["https://lemmy.ml/post/1"]);
+    assert_eq!(state.settings["show_nsfw"], true);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn instance_blocks_and_newer_settings_need_lemmy_019() {
+    let mut state = source_state("instances_old");
+    state.version = "0.19.3".to_string();
+    state.known_instances = vec!["lemmygrad.ml".to_string()];
+    state.blocked_instances = vec!["lemmygrad.ml".to_string()];
+    state.settings.insert("blur_nsfw".to_string(), true.into());
+    state.settings.insert("default_sort_type".to_string(), "Scaled".into());
+    let profile_name = download("instances_old", state).await;
+
+    let profile = engine::read_profile(&profile_name, |_| {}).unwrap();
+    assert_eq!(profile.blocked_instances, vec!["lemmygrad.ml"]);
+    assert!(profile.profile_settings.blur_nsfw);
+    assert_eq!(profile.profile_settings.default_sort_type, "Scaled");
+
+    let mut state = target_state("instances_new");
+    state.version = "0.19.3".to_string();
+    state.known_instances = vec!["lemmygrad.ml".to_string()];
+    let target = MockLemmy::start(state).await;
+    let result = engine::process_upload(instruction(&target, "instances_new", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+    {
+        let state = target.state.lock().unwrap();
+        assert_eq!(state.blocked_instances, vec!["lemmygrad.ml"]);
+        assert_eq!(state.settings["blur_nsfw"], true);
+        assert_eq!(state.settings["default_sort_type"], "Scaled");
+    }
+
+    // Lemmy 0.18 cannot block instances, so those changes are skipped rather than failed
+    let lemmy_018 = MockLemmy::start(target_state("instances_018")).await;
+    let result = engine::process_upload(instruction(&lemmy_018, "instances_018", upload_settings(), &profile_name), |_| {}, |_| true, &Cancellation::new()).await;
+    assert_eq!(result, Ok(()));
+
+    let state = lemmy_018.state.lock().unwrap();
+    assert_eq!(state.count_requests("POST /api/v3/site/block"), 0);
+    assert_eq!(state.followed_communities, vec!["rust@lemmy.ml"]);
+    assert!(!state.settings.contains_key("blur_nsfw"));
+}
@@ -0,0 +1,30 @@
+use lasim::storage;
+
+use url::Url;
+
+#[test]
+fn profile_names_stay_inside_profiles_directory() {
+    std::env::set_var("LASIM_DATA_DIR", std::env::temp_dir().join(format!("lasim-storage-{}", std::process::id())));
+    let profiles_directory = storage::get_profiles_directory().unwrap();
+
+    for profile_name in ["alice@lemmy.world", "combined", "old.account@sh.itjust.works"] {
+        assert_eq!(storage::get_profile_directory(profile_name), Ok(profiles_directory.join(profile_name)));
+    }
+    for profile_name in ["", ".", "..", "../../x", "alice/../../x", "/etc", "alice@lemmy.world/", "..\\x", "C:x"] {
+        assert!(storage::get_profile_directory(profile_name).is_err(), "{} should be rejected", profile_name);
+        assert!(storage::create_profile_directory(profile_name).is_err(), "{} should be rejected", profile_name);
+    }
+}
+
+#[test]
+fn profile_names_keep_the_port() {
+    let username = "alice";
+    assert_eq!(storage::profile_name(username, &Url::parse("https://lemmy.world/").unwrap()), "alice@lemmy.world");
+    assert_eq!(storage::profile_name(username, &Url::parse("https://lemmy.world:443/").unwrap()), "alice@lemmy.world");
+
+    let first = storage::profile_name(username, &Url::parse("http://127.0.0.1:8536/").unwrap());
+    let second = storage::profile_name(username, &Url::parse("http://127.0.0.1:8537/").unwrap());
+    assert_eq!(first, "alice@127.0.0.1_8536");
+    assert_ne!(first, second);
+    assert_eq!(storage::validate_profile_name(&first), Ok(()));
+}
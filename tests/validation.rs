@@ -0,0 +1,89 @@
+use lasim::migrations::profile_migrate;
+use lasim::validation;
+use lasim::validation::Severity;
+
+use serde_json::json;
+
+fn profile() -> lasim::profile::ProfileConfiguration {
+    let profile_json = json!({
+        "blocked_users": ["spammer@lemmy.world"],
+        "blocked_communities": ["memes@lemmy.world"],
+        "blocked_instances": ["lemmy.ml"],
+        "followed_communities": ["rust@lemmy.ml"],
+        "profile_settings": {
+            "show_nsfw": false,
+            "show_scores": true,
+            "theme": "browser",
+            "default_sort_type": "Scaled",
+            "default_listing_type": "Local",
+            "interface_language": "browser",
+            "show_avatars": true,
+            "send_notifications_to_email": false,
+            "bot_account": false,
+            "show_bot_accounts": true,
+            "show_read_posts": true,
+            "show_new_post_notifs": false,
+            "discussion_languages": [37],
+            "open_links_in_new_tab": false,
+            "infinite_scroll_enabled": true,
+        },
+        "saved_posts": ["https://lemmy.ml/post/1"],
+    });
+
+    return profile_migrate::migrate_profile(profile_json, 3).unwrap();
+}
+
+#[test]
+fn valid_profile_has_no_findings() {
+    assert_eq!(validation::validate_profile(&profile()), validation::ValidationReport::default());
+}
+
+#[test]
+fn unknown_settings_are_errors() {
+    let mut profile = profile();
+    profile.profile_settings.default_sort_type = "Sideways".to_string();
+    profile.profile_settings.default_listing_type = "Everything".to_string();
+
+    let report = validation::validate_profile(&profile);
+    assert_eq!(report.error_count(), 2);
+    assert_eq!(report.warning_count(), 0);
+    assert_eq!(report.findings[0].to_string(), "ERROR: profile_settings.default_sort_type - Sideways is not a sort type Lemmy knows");
+    assert_eq!(report.findings[1].field, "profile_settings.default_listing_type");
+}
+
+#[test]
+fn malformed_entries_are_warnings_and_removed_for_upload() {
+    let mut profile = profile();
+    profile.blocked_users.push("spammer".to_string());
+    profile.blocked_instances.push("https://lemmy.ml/".to_string());
+
+    let report = validation::validate_profile(&profile);
+    assert!(!report.has_errors());
+    assert_eq!(report.warning_count(), 2);
+    assert_eq!(report.findings[0].to_string(), "WARNING: blocked_users - spammer is not written as name@host, it is skipped when uploading");
+    assert_eq!(report.findings[1].field, "blocked_instances");
+
+    let removed_entries = validation::remove_malformed_entries(&mut profile);
+    assert_eq!(removed_entries, vec!["blocked_users: spammer", "blocked_instances: https://lemmy.ml/"]);
+    assert_eq!(profile.blocked_users, vec!["spammer@lemmy.world"]);
+    assert_eq!(profile.blocked_instances, vec!["lemmy.ml"]);
+    assert_eq!(validation::validate_profile(&profile), validation::ValidationReport::default());
+}
+
+#[test]
+fn duplicates_and_contradictions_are_warnings() {
+    let mut profile = profile();
+    profile.followed_communities.push("Rust@lemmy.ml".to_string());
+    profile.followed_communities.push("memes@lemmy.world".to_string());
+    profile.saved_posts.push("https://lemmy.ml/post/1".to_string());
+
+    let report = validation::validate_profile(&profile);
+    assert!(!report.has_errors());
+    assert!(report.findings.iter().all(|finding| finding.severity == Severity::Warning));
+    let messages: Vec<String> = report.findings.iter().map(|finding| finding.message.clone()).collect();
+    assert_eq!(messages, vec![
+        "Rust@lemmy.ml is listed more than once",
+        "https://lemmy.ml/post/1 is listed more than once",
+        "memes@lemmy.world is both followed and blocked",
+    ]);
+}